@@ -0,0 +1,369 @@
+//! Arrow IPC files of events and book snapshots.
+
+use crate::{event::Event, level::Level, orderbook::Orderbook, sampler::Sample};
+use arrow_array::{Array, ArrayRef, BooleanArray, Float64Array, RecordBatch, UInt64Array};
+use arrow_ipc::{reader::FileReader, writer::FileWriter};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use std::io::{Read, Seek, Write};
+use std::sync::Arc;
+
+/// Rows buffered before a record batch is written.
+const BATCH_SIZE: usize = 65_536;
+
+/// Schema of events, with the columns of the CSV files.
+pub fn event_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("timestamp", DataType::UInt64, false),
+        Field::new("seq", DataType::UInt64, false),
+        Field::new("is_trade", DataType::Boolean, false),
+        Field::new("is_buy", DataType::Boolean, false),
+        Field::new("price", DataType::Float64, false),
+        Field::new("size", DataType::Float64, false),
+    ])
+}
+
+/// Schema of snapshots of the top `levels` levels, with null prices and sizes for missing
+/// levels.
+pub fn snapshot_schema(levels: usize) -> Schema {
+    let mut fields = vec![
+        Field::new("timestamp", DataType::UInt64, false),
+        Field::new("seq", DataType::UInt64, false),
+    ];
+
+    for side in ["bid", "ask"] {
+        for i in 0..levels {
+            fields.push(Field::new(
+                format!("{side}_price_{i}"),
+                DataType::Float64,
+                true,
+            ));
+            fields.push(Field::new(
+                format!("{side}_size_{i}"),
+                DataType::Float64,
+                true,
+            ));
+        }
+    }
+
+    Schema::new(fields)
+}
+
+/// Writes events to an Arrow IPC file in record batches.
+pub struct EventWriter<W: Write> {
+    writer: FileWriter<W>,
+    schema: SchemaRef,
+    events: Vec<Event>,
+}
+
+impl<W: Write> EventWriter<W> {
+    pub fn new(writer: W) -> Result<Self, ArrowError> {
+        let schema = Arc::new(event_schema());
+
+        Ok(Self {
+            writer: FileWriter::try_new(writer, &schema)?,
+            schema,
+            events: Vec::with_capacity(BATCH_SIZE),
+        })
+    }
+
+    pub fn write(&mut self, event: Event) -> Result<(), ArrowError> {
+        self.events.push(event);
+
+        if self.events.len() == BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), ArrowError> {
+        if self.events.is_empty() {
+            return Ok(());
+        }
+
+        let column = |f: fn(&Event) -> u64| -> ArrayRef {
+            Arc::new(UInt64Array::from_iter_values(self.events.iter().map(f)))
+        };
+        let flag = |f: fn(&Event) -> bool| -> ArrayRef {
+            Arc::new(BooleanArray::from_iter(
+                self.events.iter().map(|e| Some(f(e))),
+            ))
+        };
+        let value = |f: fn(&Event) -> f64| -> ArrayRef {
+            Arc::new(Float64Array::from_iter_values(self.events.iter().map(f)))
+        };
+
+        let batch = RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                column(|e| e.timestamp),
+                column(|e| e.seq),
+                flag(|e| e.is_trade),
+                flag(|e| e.is_buy),
+                value(|e| e.price),
+                value(|e| e.size),
+            ],
+        )?;
+
+        self.writer.write(&batch)?;
+        self.events.clear();
+        Ok(())
+    }
+
+    /// Writes the remaining events and the file footer, and returns the writer.
+    pub fn finish(mut self) -> Result<W, ArrowError> {
+        self.flush()?;
+        self.writer.finish()?;
+        self.writer.into_inner()
+    }
+}
+
+/// Writes snapshots of the top levels of a book to an Arrow IPC file in record batches.
+pub struct SnapshotWriter<W: Write> {
+    writer: FileWriter<W>,
+    schema: SchemaRef,
+    levels: usize,
+    timestamps: Vec<u64>,
+    seqs: Vec<u64>,
+    /// Bid then ask levels of each snapshot.
+    rows: Vec<Option<Level>>,
+}
+
+impl<W: Write> SnapshotWriter<W> {
+    pub fn new(writer: W, levels: usize) -> Result<Self, ArrowError> {
+        let schema = Arc::new(snapshot_schema(levels));
+
+        Ok(Self {
+            writer: FileWriter::try_new(writer, &schema)?,
+            schema,
+            levels,
+            timestamps: Vec::new(),
+            seqs: Vec::new(),
+            rows: Vec::new(),
+        })
+    }
+
+    /// Writes the state of `book`, usually right after it processed the event with the given
+    /// `timestamp` and `seq`.
+    pub fn write(&mut self, timestamp: u64, seq: u64, book: &Orderbook) -> Result<(), ArrowError> {
+        self.push(timestamp, seq, book.bids(), book.asks())
+    }
+
+    /// Writes a sample of a book, with the sequence number of the grid point.
+    pub fn write_sample(&mut self, seq: u64, sample: &Sample) -> Result<(), ArrowError> {
+        self.push(
+            sample.timestamp,
+            seq,
+            sample.bids.iter(),
+            sample.asks.iter(),
+        )
+    }
+
+    fn push<'a>(
+        &mut self,
+        timestamp: u64,
+        seq: u64,
+        mut bids: impl Iterator<Item = &'a Level>,
+        mut asks: impl Iterator<Item = &'a Level>,
+    ) -> Result<(), ArrowError> {
+        self.timestamps.push(timestamp);
+        self.seqs.push(seq);
+
+        let levels = self.levels;
+        let rows = &mut self.rows;
+        let mut pad = |side: &mut dyn Iterator<Item = &'a Level>| {
+            let start = rows.len();
+            rows.extend(side.take(levels).map(|level| Some(*level)));
+            rows.resize(start + levels, None);
+        };
+        pad(&mut bids);
+        pad(&mut asks);
+
+        if self.timestamps.len() == BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), ArrowError> {
+        if self.timestamps.is_empty() {
+            return Ok(());
+        }
+
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from(std::mem::take(&mut self.timestamps))),
+            Arc::new(UInt64Array::from(std::mem::take(&mut self.seqs))),
+        ];
+
+        let width = self.levels * 2;
+        for i in 0..width {
+            let levels = self.rows.iter().skip(i).step_by(width.max(1));
+            columns.push(Arc::new(Float64Array::from_iter(
+                levels.clone().map(|level| level.map(|level| level.price)),
+            )));
+            columns.push(Arc::new(Float64Array::from_iter(
+                levels.map(|level| level.map(|level| level.size)),
+            )));
+        }
+
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        self.writer.write(&batch)?;
+        self.rows.clear();
+        Ok(())
+    }
+
+    /// Writes the remaining snapshots and the file footer, and returns the writer.
+    pub fn finish(mut self) -> Result<W, ArrowError> {
+        self.flush()?;
+        self.writer.finish()?;
+        self.writer.into_inner()
+    }
+}
+
+/// Iterator over the events of an Arrow IPC file written with [`event_schema`].
+pub struct EventReader<R: Read + Seek> {
+    reader: FileReader<R>,
+    batch: Option<RecordBatch>,
+    row: usize,
+}
+
+/// Reads events from an Arrow IPC file, checking that it has the columns of [`event_schema`].
+pub fn read_events<R: Read + Seek>(reader: R) -> Result<EventReader<R>, ArrowError> {
+    let reader = FileReader::try_new(reader, None)?;
+
+    let schema = reader.schema();
+    for expected in event_schema().fields() {
+        let field = schema.field_with_name(expected.name())?;
+        if field.data_type() != expected.data_type() {
+            return Err(ArrowError::SchemaError(format!(
+                "column `{}` is {}, expected {}",
+                expected.name(),
+                field.data_type(),
+                expected.data_type()
+            )));
+        }
+    }
+
+    Ok(EventReader {
+        reader,
+        batch: None,
+        row: 0,
+    })
+}
+
+fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> &'a T {
+    batch
+        .column_by_name(name)
+        .and_then(|column| column.as_any().downcast_ref::<T>())
+        .expect("columns are checked when the reader is created")
+}
+
+impl<R: Read + Seek> Iterator for EventReader<R> {
+    type Item = Result<Event, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(batch) = &self.batch {
+                if self.row < batch.num_rows() {
+                    let row = self.row;
+                    self.row += 1;
+
+                    let timestamps = column::<UInt64Array>(batch, "timestamp");
+                    let seqs = column::<UInt64Array>(batch, "seq");
+                    let is_trades = column::<BooleanArray>(batch, "is_trade");
+                    let is_buys = column::<BooleanArray>(batch, "is_buy");
+                    let prices = column::<Float64Array>(batch, "price");
+                    let sizes = column::<Float64Array>(batch, "size");
+
+                    let columns: [&dyn Array; 6] =
+                        [timestamps, seqs, is_trades, is_buys, prices, sizes];
+                    if columns.iter().any(|column| column.is_null(row)) {
+                        return Some(Err(ArrowError::InvalidArgumentError(format!(
+                            "null value in event {row} of a batch"
+                        ))));
+                    }
+
+                    return Some(Ok(Event {
+                        timestamp: timestamps.value(row),
+                        seq: seqs.value(row),
+                        is_trade: is_trades.value(row),
+                        is_buy: is_buys.value(row),
+                        price: prices.value(row),
+                        size: sizes.value(row),
+                    }));
+                }
+            }
+
+            match self.reader.next()? {
+                Ok(batch) => {
+                    self.batch = Some(batch);
+                    self.row = 0;
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn events_round_trip() {
+        let events: Vec<Event> = (0..5)
+            .map(|i| Event {
+                timestamp: i * 10,
+                seq: i,
+                is_trade: i % 2 == 1,
+                is_buy: i < 3,
+                price: 100.0 + i as f64,
+                size: 0.5,
+            })
+            .collect();
+
+        let mut writer = EventWriter::new(Vec::new()).unwrap();
+        events
+            .iter()
+            .for_each(|&event| writer.write(event).unwrap());
+        let bytes = writer.finish().unwrap();
+
+        let read: Vec<Event> = read_events(Cursor::new(bytes))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(read, events);
+    }
+
+    #[test]
+    fn snapshots() {
+        let mut book = Orderbook::new(0.01);
+        let mut writer = SnapshotWriter::new(Vec::new(), 2).unwrap();
+
+        let event = Event {
+            timestamp: 1,
+            seq: 1,
+            is_trade: false,
+            is_buy: true,
+            price: 99.0,
+            size: 1.0,
+        };
+        book.process(event);
+        writer.write(event.timestamp, event.seq, &book).unwrap();
+
+        let bytes = writer.finish().unwrap();
+        let mut reader = FileReader::try_new(Cursor::new(bytes), None).unwrap();
+
+        assert_eq!(*reader.schema(), snapshot_schema(2));
+
+        let batch = reader.next().unwrap().unwrap();
+        let bid = column::<Float64Array>(&batch, "bid_price_0");
+        let ask = column::<Float64Array>(&batch, "ask_size_0");
+
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(bid.value(0), 99.0);
+        assert!(ask.is_null(0));
+        assert!(read_events(Cursor::new(Vec::<u8>::new())).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Tests shared by the level 2 backends, so they are held to the same behavior.
+
+/// Expands to the shared tests for the backend built by `$new`.
+macro_rules! backend_tests {
+    ($new:expr) => {
+        #[test]
+        fn delta_updates() {
+            use $crate::{config::UpdateMode, event::Event, level::Level};
+
+            let mut ob = $new.with_update_mode(UpdateMode::Delta);
+
+            let mut event = Event {
+                timestamp: 0,
+                seq: 0,
+                is_trade: false,
+                is_buy: true,
+                price: 16.0,
+                size: 2.0,
+            };
+
+            ob.process(event);
+
+            event.seq = 1;
+            event.size = 1.5;
+            ob.process(event);
+
+            assert_eq!(
+                ob.best_bid().unwrap(),
+                Level {
+                    price: 16.0,
+                    size: 3.5
+                }
+            );
+
+            event.seq = 2;
+            event.size = -1.0;
+            ob.process(event);
+
+            assert_eq!(ob.best_bid().unwrap().size, 2.5);
+
+            event.seq = 3;
+            event.size = -4.0;
+            ob.process(event);
+
+            assert_eq!(ob.best_bid(), None);
+        }
+
+        #[test]
+        fn trade_modes() {
+            use $crate::{config::TradeMode, event::Event};
+
+            let bid = Event {
+                timestamp: 0,
+                seq: 0,
+                is_trade: false,
+                is_buy: true,
+                price: 16.0,
+                size: 2.0,
+            };
+            let ask = Event {
+                seq: 1,
+                is_buy: false,
+                price: 17.0,
+                ..bid
+            };
+            let buy = Event {
+                seq: 2,
+                is_trade: true,
+                is_buy: true,
+                price: 17.0,
+                size: 2.0,
+                ..bid
+            };
+
+            let mut ob = $new.with_trade_mode(TradeMode::Aggressor);
+            ob.process(bid);
+            ob.process(ask);
+            ob.process(buy);
+
+            assert_eq!(ob.best_bid().unwrap().size, 2.0);
+            assert_eq!(ob.best_ask(), None);
+
+            let mut ob = $new.with_trade_mode(TradeMode::Ignore);
+            ob.process(bid);
+            ob.process(ask);
+            ob.process(buy);
+
+            assert_eq!(ob.best_ask().unwrap().size, 2.0);
+        }
+
+        #[test]
+        fn sweep() {
+            use $crate::{config::TradeMode, event::Event, level::Level};
+
+            let mut ob = $new.with_trade_mode(TradeMode::Aggressor).with_sweep(true);
+
+            [17.0, 18.0, 19.0]
+                .into_iter()
+                .enumerate()
+                .for_each(|(i, price)| {
+                    ob.process(Event {
+                        timestamp: 0,
+                        seq: i as u64,
+                        is_trade: false,
+                        is_buy: false,
+                        price,
+                        size: 2.0,
+                    })
+                });
+
+            ob.process(Event {
+                timestamp: 0,
+                seq: 3,
+                is_trade: true,
+                is_buy: true,
+                price: 18.0,
+                size: 1.0,
+            });
+
+            assert_eq!(
+                ob.top_asks(5),
+                [
+                    Level {
+                        price: 18.0,
+                        size: 1.0
+                    },
+                    Level {
+                        price: 19.0,
+                        size: 2.0
+                    },
+                ]
+            );
+            assert_eq!(ob.best_ask().unwrap().price, 18.0);
+
+            ob.process(Event {
+                timestamp: 0,
+                seq: 4,
+                is_trade: true,
+                is_buy: true,
+                price: 18.5,
+                size: 1.0,
+            });
+
+            assert_eq!(
+                ob.top_asks(5),
+                [Level {
+                    price: 19.0,
+                    size: 2.0
+                }]
+            );
+        }
+
+        #[test]
+        fn side_iterators() {
+            use $crate::{event::Event, level::Level};
+
+            let mut ob = $new;
+
+            [
+                (true, 99.0),
+                (true, 98.0),
+                (true, 97.0),
+                (false, 101.0),
+                (false, 102.0),
+            ]
+            .into_iter()
+            .enumerate()
+            .for_each(|(i, (is_buy, price))| {
+                ob.process(Event {
+                    timestamp: 0,
+                    seq: i as u64,
+                    is_trade: false,
+                    is_buy,
+                    price,
+                    size: 1.0,
+                })
+            });
+
+            assert_eq!(
+                ob.bids().map(|level| level.price).collect::<Vec<_>>(),
+                [99.0, 98.0, 97.0]
+            );
+            assert_eq!(
+                ob.asks().map(|level| level.price).collect::<Vec<_>>(),
+                [101.0, 102.0]
+            );
+            assert_eq!(
+                ob.bids_range(97.5, 98.5)
+                    .map(|level| level.price)
+                    .collect::<Vec<_>>(),
+                [98.0]
+            );
+            assert_eq!(ob.asks_range(101.0, 101.5).count(), 1);
+            assert_eq!(ob.asks_range(102.0, 101.0).count(), 0);
+            assert_eq!(ob.bid_at(98.0), Some(&Level::new(98.0, 1.0)));
+            assert_eq!(ob.ask_at(98.0), None);
+            assert_eq!(ob.bids_len(), 3);
+            assert_eq!(ob.asks_len(), 2);
+        }
+    };
+}
+
+pub(crate) use backend_tests;
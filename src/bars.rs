@@ -0,0 +1,263 @@
+use crate::{event::Event, orderbook::Orderbook};
+
+/// When a bar closes and which prices it is built from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BarKind {
+    /// Trade prices over intervals of this many microseconds, aligned to the epoch.
+    Time(u64),
+    /// Trade prices over this many trades.
+    Tick(u64),
+    /// Trade prices until the traded size reaches this volume.
+    Volume(f64),
+    /// Trade prices until the traded notional reaches this amount.
+    Dollar(f64),
+    /// Midprices of the book over intervals of this many microseconds, aligned to the epoch.
+    Midprice(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bar {
+    pub start: u64,
+    /// End of the interval for time based bars, otherwise the timestamp of the last event.
+    pub end: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub notional: f64,
+    pub trades: u64,
+    /// Spread and sizes of the best levels when the bar closed.
+    pub spread: Option<f64>,
+    pub bid_size: Option<f64>,
+    pub ask_size: Option<f64>,
+}
+
+impl Bar {
+    fn new(start: u64, price: f64) -> Self {
+        Self {
+            start,
+            end: start,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: 0.0,
+            notional: 0.0,
+            trades: 0,
+            spread: None,
+            bid_size: None,
+            ask_size: None,
+        }
+    }
+
+    pub fn vwap(&self) -> Option<f64> {
+        (self.volume > 0.0).then(|| self.notional / self.volume)
+    }
+}
+
+/// Builds bars from the events fed to a book.
+///
+/// Threshold bars close on the trade that reaches the threshold, which is not split across bars.
+/// Time based bars close on the first event of a later interval, and intervals without prices
+/// produce no bar.
+#[derive(Debug, Clone)]
+pub struct BarBuilder {
+    book: Orderbook,
+    kind: BarKind,
+    current: Option<Bar>,
+}
+
+impl BarBuilder {
+    pub fn new(book: Orderbook, kind: BarKind) -> Self {
+        Self {
+            book,
+            kind,
+            current: None,
+        }
+    }
+
+    pub fn book(&self) -> &Orderbook {
+        &self.book
+    }
+
+    pub fn into_inner(self) -> Orderbook {
+        self.book
+    }
+
+    /// Processes an event, returning the bar it closed, if any.
+    pub fn process(&mut self, event: Event) -> Option<Bar> {
+        let mut closed = None;
+
+        if let (BarKind::Time(interval) | BarKind::Midprice(interval), Some(bar)) =
+            (self.kind, &self.current)
+        {
+            if event.timestamp >= bar.start + interval {
+                closed = self.close(None);
+            }
+        }
+
+        self.book.process(event);
+
+        let price = match self.kind {
+            BarKind::Midprice(_) => self.book.midprice(),
+            _ => event.is_trade.then_some(event.price),
+        };
+        let Some(price) = price else {
+            return closed;
+        };
+
+        let start = match self.kind {
+            BarKind::Time(interval) | BarKind::Midprice(interval) => {
+                event.timestamp - event.timestamp % interval.max(1)
+            }
+            _ => event.timestamp,
+        };
+
+        let bar = self.current.get_or_insert_with(|| Bar::new(start, price));
+        bar.high = bar.high.max(price);
+        bar.low = bar.low.min(price);
+        bar.close = price;
+        bar.end = event.timestamp;
+
+        if event.is_trade {
+            bar.volume += event.size;
+            bar.notional += event.price * event.size;
+            bar.trades += 1;
+        }
+
+        let full = match self.kind {
+            BarKind::Tick(n) => bar.trades >= n,
+            BarKind::Volume(volume) => bar.volume >= volume,
+            BarKind::Dollar(notional) => bar.notional >= notional,
+            BarKind::Time(_) | BarKind::Midprice(_) => false,
+        };
+
+        if full {
+            closed = self.close(Some(event.timestamp));
+        }
+
+        closed
+    }
+
+    /// Closes the bar in progress, if any.
+    pub fn flush(&mut self) -> Option<Bar> {
+        self.close(None)
+    }
+
+    fn close(&mut self, end: Option<u64>) -> Option<Bar> {
+        let mut bar = self.current.take()?;
+
+        bar.end = match (self.kind, end) {
+            (BarKind::Time(interval) | BarKind::Midprice(interval), _) => bar.start + interval,
+            (_, Some(end)) => end,
+            (_, None) => bar.end,
+        };
+
+        let bid = self.book.best_bid();
+        let ask = self.book.best_ask();
+        bar.spread = bid.zip(ask).map(|(bid, ask)| ask.price - bid.price);
+        bar.bid_size = bid.map(|bid| bid.size);
+        bar.ask_size = ask.map(|ask| ask.size);
+
+        Some(bar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(seq: u64, is_buy: bool, price: f64, size: f64) -> Event {
+        Event {
+            timestamp: seq,
+            seq,
+            is_trade: false,
+            is_buy,
+            price,
+            size,
+        }
+    }
+
+    fn trade(timestamp: u64, price: f64, size: f64) -> Event {
+        Event {
+            timestamp,
+            seq: timestamp,
+            is_trade: true,
+            is_buy: true,
+            price,
+            size,
+        }
+    }
+
+    #[test]
+    fn time_bars() {
+        let mut builder = BarBuilder::new(Orderbook::new(0.01), BarKind::Time(10));
+
+        assert_eq!(builder.process(quote(1, true, 99.0, 5.0)), None);
+        assert_eq!(builder.process(quote(2, false, 101.0, 5.0)), None);
+        assert_eq!(builder.process(trade(3, 100.0, 1.0)), None);
+        assert_eq!(builder.process(trade(5, 102.0, 2.0)), None);
+        assert_eq!(builder.process(trade(7, 98.0, 1.0)), None);
+
+        let bar = builder.process(trade(25, 99.0, 1.0)).unwrap();
+
+        assert_eq!((bar.start, bar.end), (0, 10));
+        assert_eq!(
+            (bar.open, bar.high, bar.low, bar.close),
+            (100.0, 102.0, 98.0, 98.0)
+        );
+        assert_eq!((bar.volume, bar.trades), (4.0, 3));
+        assert_eq!(bar.vwap(), Some(100.5));
+        assert_eq!(bar.spread, Some(2.0));
+        assert_eq!(bar.bid_size, Some(5.0));
+
+        let bar = builder.flush().unwrap();
+
+        assert_eq!((bar.start, bar.end), (20, 30));
+        assert_eq!(builder.flush(), None);
+    }
+
+    #[test]
+    fn threshold_bars() {
+        let mut builder = BarBuilder::new(Orderbook::new(0.01), BarKind::Volume(3.0));
+
+        assert_eq!(builder.process(trade(1, 100.0, 2.0)), None);
+        let bar = builder.process(trade(2, 101.0, 2.0)).unwrap();
+
+        assert_eq!((bar.start, bar.end, bar.volume), (1, 2, 4.0));
+        assert_eq!(bar.spread, None);
+
+        let mut builder = BarBuilder::new(Orderbook::new(0.01), BarKind::Tick(2));
+
+        assert_eq!(builder.process(trade(1, 100.0, 2.0)), None);
+        assert_eq!(builder.process(trade(2, 101.0, 2.0)).unwrap().trades, 2);
+
+        let mut builder = BarBuilder::new(Orderbook::new(0.01), BarKind::Dollar(250.0));
+
+        assert_eq!(builder.process(trade(1, 100.0, 2.0)), None);
+        assert_eq!(
+            builder.process(trade(2, 100.0, 1.0)).unwrap().notional,
+            300.0
+        );
+    }
+
+    #[test]
+    fn midprice_bars() {
+        let mut builder = BarBuilder::new(Orderbook::new(0.01), BarKind::Midprice(10));
+
+        builder.process(quote(1, true, 99.0, 5.0));
+        builder.process(quote(2, false, 101.0, 5.0));
+        builder.process(quote(3, false, 103.0, 5.0));
+        builder.process(quote(4, false, 101.0, 0.0));
+
+        let bar = builder.process(quote(12, true, 100.0, 1.0)).unwrap();
+
+        assert_eq!(
+            (bar.open, bar.high, bar.low, bar.close),
+            (100.0, 101.0, 100.0, 101.0)
+        );
+        assert_eq!(bar.spread, Some(4.0));
+        assert_eq!(builder.flush().unwrap().open, 101.5);
+    }
+}
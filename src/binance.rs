@@ -0,0 +1,472 @@
+use crate::{event::Event, level::Level, orderbook::Orderbook};
+use serde::de::{Deserializer, Error};
+use serde::Deserialize;
+use std::io::Read;
+
+/// Binance `depthUpdate` message from the diff. depth stream (spot or futures).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DepthUpdate {
+    /// Event time in milliseconds.
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+    /// Final update id of the previous message, only sent by the futures streams.
+    #[serde(rename = "pu", default)]
+    pub previous_final_update_id: Option<u64>,
+    #[serde(rename = "b", deserialize_with = "deserialize_levels")]
+    pub bids: Vec<Level>,
+    #[serde(rename = "a", deserialize_with = "deserialize_levels")]
+    pub asks: Vec<Level>,
+}
+
+impl DepthUpdate {
+    /// Parses a raw `depthUpdate` message or one wrapped in a combined stream payload.
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str::<Message>(json).map(Message::into_update)
+    }
+
+    /// Level 2 events of the update, timestamped in microseconds and sequenced by `u`.
+    pub fn events(&self) -> impl Iterator<Item = Event> + '_ {
+        level_events(
+            &self.bids,
+            &self.asks,
+            self.event_time * 1000,
+            self.final_update_id,
+        )
+    }
+}
+
+/// REST depth snapshot (`GET /api/v3/depth` or `GET /fapi/v1/depth`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DepthSnapshot {
+    #[serde(rename = "lastUpdateId")]
+    pub last_update_id: u64,
+    /// Message time in milliseconds, only sent by the futures endpoints.
+    #[serde(rename = "E", default)]
+    pub event_time: Option<u64>,
+    #[serde(deserialize_with = "deserialize_levels")]
+    pub bids: Vec<Level>,
+    #[serde(deserialize_with = "deserialize_levels")]
+    pub asks: Vec<Level>,
+}
+
+impl DepthSnapshot {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// Level 2 events rebuilding the snapshot, sequenced by `lastUpdateId`.
+    pub fn events(&self) -> impl Iterator<Item = Event> + '_ {
+        level_events(
+            &self.bids,
+            &self.asks,
+            self.event_time.unwrap_or(0) * 1000,
+            self.last_update_id,
+        )
+    }
+}
+
+/// Reads a recording of `depthUpdate` messages, one JSON document per line
+/// (raw or wrapped in a combined stream payload).
+pub fn read_updates<R: Read>(reader: R) -> impl Iterator<Item = serde_json::Result<DepthUpdate>> {
+    serde_json::Deserializer::from_reader(reader)
+        .into_iter::<Message>()
+        .map(|message| message.map(Message::into_update))
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Message {
+    Combined { data: DepthUpdate },
+    Raw(DepthUpdate),
+}
+
+impl Message {
+    fn into_update(self) -> DepthUpdate {
+        match self {
+            Message::Combined { data } => data,
+            Message::Raw(update) => update,
+        }
+    }
+}
+
+fn deserialize_levels<'de, D>(deserializer: D) -> Result<Vec<Level>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = Vec::<(String, String)>::deserialize(deserializer)?;
+
+    raw.into_iter()
+        .map(|(price, size)| {
+            let price = price
+                .parse()
+                .map_err(|_| Error::custom(format!("invalid price {:?}", price)))?;
+            let size = size
+                .parse()
+                .map_err(|_| Error::custom(format!("invalid size {:?}", size)))?;
+            Ok(Level::new(price, size))
+        })
+        .collect()
+}
+
+fn level_events<'a>(
+    bids: &'a [Level],
+    asks: &'a [Level],
+    timestamp: u64,
+    seq: u64,
+) -> impl Iterator<Item = Event> + 'a {
+    let to_event = move |is_buy: bool| {
+        move |level: &Level| Event {
+            timestamp,
+            seq,
+            is_trade: false,
+            is_buy,
+            price: level.price,
+            size: level.size,
+        }
+    };
+
+    bids.iter()
+        .map(to_event(true))
+        .chain(asks.iter().map(to_event(false)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncState {
+    /// Updates are buffered until a snapshot is applied.
+    AwaitingSnapshot,
+    /// A snapshot was applied and the update bridging it has not been seen yet.
+    AwaitingFirstUpdate,
+    /// Updates are applied as they come.
+    Synced,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateStatus {
+    /// The update was applied to the book.
+    Applied,
+    /// The update is older than the book and was discarded.
+    Discarded,
+    /// No snapshot has been applied yet, the update was kept for later.
+    Buffered,
+    /// Updates were missed, the book is no longer valid until a new snapshot is applied.
+    ResyncRequired {
+        last_update_id: u64,
+        first_update_id: u64,
+    },
+}
+
+/// Keeps an [`Orderbook`] in sync with a Binance diff. depth stream following the documented
+/// procedure: updates with `u` <= `lastUpdateId` are discarded, the first applied update must
+/// satisfy `U` <= `lastUpdateId + 1` <= `u` and every following update must continue where the
+/// previous one ended (`U == u + 1`, or `pu == u` on futures streams).
+#[derive(Debug, Clone)]
+pub struct BinanceBook {
+    book: Orderbook,
+    state: SyncState,
+    last_update_id: u64,
+    buffer: Vec<DepthUpdate>,
+}
+
+impl BinanceBook {
+    pub fn new(tick_size: f64) -> Self {
+        Self::from_orderbook(Orderbook::new(tick_size))
+    }
+
+    /// Wraps an already configured book, which is cleared by every snapshot.
+    pub fn from_orderbook(book: Orderbook) -> Self {
+        Self {
+            book,
+            state: SyncState::AwaitingSnapshot,
+            last_update_id: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn book(&self) -> &Orderbook {
+        &self.book
+    }
+
+    pub fn into_inner(self) -> Orderbook {
+        self.book
+    }
+
+    pub fn state(&self) -> SyncState {
+        self.state
+    }
+
+    /// Final update id applied to the book, or the snapshot `lastUpdateId`.
+    pub fn last_update_id(&self) -> u64 {
+        self.last_update_id
+    }
+
+    /// Replaces the book with `snapshot` and replays the updates buffered while waiting for it.
+    /// Returns the status of the last replayed update, if any.
+    pub fn apply_snapshot(&mut self, snapshot: &DepthSnapshot) -> Option<UpdateStatus> {
+        self.book.clear();
+        snapshot.events().for_each(|event| self.book.process(event));
+
+        self.last_update_id = snapshot.last_update_id;
+        self.state = SyncState::AwaitingFirstUpdate;
+
+        let mut status = None;
+        let mut buffered = std::mem::take(&mut self.buffer).into_iter();
+        for update in buffered.by_ref() {
+            let replayed = self.apply_update(&update);
+            status = Some(replayed);
+            if let UpdateStatus::ResyncRequired { .. } = replayed {
+                // keep what is left for the next snapshot
+                self.buffer.extend(buffered);
+                break;
+            }
+        }
+
+        status
+    }
+
+    pub fn apply_update(&mut self, update: &DepthUpdate) -> UpdateStatus {
+        match self.state {
+            SyncState::AwaitingSnapshot => {
+                self.buffer.push(update.clone());
+                return UpdateStatus::Buffered;
+            }
+            SyncState::AwaitingFirstUpdate => {
+                // futures streams bridge the snapshot with `U <= lastUpdateId <= u`, spot streams
+                // with `U <= lastUpdateId + 1 <= u`
+                let (stale, target) = match update.previous_final_update_id {
+                    Some(_) => (
+                        update.final_update_id < self.last_update_id,
+                        self.last_update_id,
+                    ),
+                    None => (
+                        update.final_update_id <= self.last_update_id,
+                        self.last_update_id + 1,
+                    ),
+                };
+
+                if stale {
+                    return UpdateStatus::Discarded;
+                }
+
+                if update.first_update_id > target {
+                    return self.resync(update);
+                }
+            }
+            SyncState::Synced => {
+                if update.final_update_id <= self.last_update_id {
+                    return UpdateStatus::Discarded;
+                }
+
+                let continuous = match update.previous_final_update_id {
+                    Some(previous) => previous == self.last_update_id,
+                    None => update.first_update_id == self.last_update_id + 1,
+                };
+
+                if !continuous {
+                    return self.resync(update);
+                }
+            }
+        }
+
+        update.events().for_each(|event| self.book.process(event));
+
+        self.last_update_id = update.final_update_id;
+        self.state = SyncState::Synced;
+
+        UpdateStatus::Applied
+    }
+
+    fn resync(&mut self, update: &DepthUpdate) -> UpdateStatus {
+        let status = UpdateStatus::ResyncRequired {
+            last_update_id: self.last_update_id,
+            first_update_id: update.first_update_id,
+        };
+
+        self.state = SyncState::AwaitingSnapshot;
+        self.buffer.clear();
+        self.buffer.push(update.clone());
+
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> DepthSnapshot {
+        DepthSnapshot::from_json(
+            r#"{"lastUpdateId":100,"bids":[["10.00","1.0"],["9.00","2.0"]],"asks":[["11.00","1.5"]]}"#,
+        )
+        .unwrap()
+    }
+
+    fn update(first: u64, last: u64, bids: &str, asks: &str) -> DepthUpdate {
+        DepthUpdate::from_json(&format!(
+            r#"{{"e":"depthUpdate","E":1700000000000,"s":"BTCUSDT","U":{},"u":{},"b":{},"a":{}}}"#,
+            first, last, bids, asks
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_messages() {
+        let update = DepthUpdate::from_json(
+            r#"{"stream":"btcusdt@depth","data":{"e":"depthUpdate","E":1700000000123,"s":"BTCUSDT","U":157,"u":160,"b":[["0.0024","10"]],"a":[["0.0026","100"],["0.0027","0"]]}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(update.first_update_id, 157);
+        assert_eq!(update.final_update_id, 160);
+        assert_eq!(update.bids, [Level::new(0.0024, 10.0)]);
+
+        let events = update.events().collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].timestamp, 1700000000123000);
+        assert_eq!(events[0].seq, 160);
+        assert!(events[0].is_buy);
+        assert!(!events[2].is_buy);
+        assert_eq!(events[2].size, 0.0);
+
+        let snapshot = snapshot();
+
+        assert_eq!(snapshot.last_update_id, 100);
+        assert_eq!(snapshot.events().count(), 3);
+    }
+
+    #[test]
+    fn read_recording() {
+        let data = format!(
+            "{}\n{}\n",
+            r#"{"e":"depthUpdate","E":1,"s":"BTCUSDT","U":1,"u":2,"b":[],"a":[]}"#,
+            r#"{"stream":"btcusdt@depth","data":{"e":"depthUpdate","E":2,"s":"BTCUSDT","U":3,"u":4,"b":[],"a":[]}}"#
+        );
+
+        let updates = read_updates(data.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[1].final_update_id, 4);
+    }
+
+    #[test]
+    fn sync_procedure() {
+        let mut book = BinanceBook::new(0.01);
+
+        assert_eq!(
+            book.apply_update(&update(90, 99, "[]", "[]")),
+            UpdateStatus::Buffered
+        );
+        assert_eq!(
+            book.apply_update(&update(100, 102, r#"[["10.00","3.0"]]"#, "[]")),
+            UpdateStatus::Buffered
+        );
+
+        assert_eq!(
+            book.apply_snapshot(&snapshot()),
+            Some(UpdateStatus::Applied)
+        );
+        assert_eq!(book.state(), SyncState::Synced);
+        assert_eq!(book.last_update_id(), 102);
+        assert_eq!(book.book().best_bid(), Some(Level::new(10.0, 3.0)));
+
+        assert_eq!(
+            book.apply_update(&update(103, 105, "[]", r#"[["10.50","1.0"]]"#)),
+            UpdateStatus::Applied
+        );
+        assert_eq!(book.book().best_ask(), Some(Level::new(10.5, 1.0)));
+
+        assert_eq!(
+            book.apply_update(&update(101, 104, "[]", "[]")),
+            UpdateStatus::Discarded
+        );
+    }
+
+    #[test]
+    fn gap_requires_resync() {
+        let mut book = BinanceBook::new(0.01);
+
+        assert_eq!(book.apply_snapshot(&snapshot()), None);
+        assert_eq!(book.state(), SyncState::AwaitingFirstUpdate);
+
+        assert_eq!(
+            book.apply_update(&update(101, 103, "[]", "[]")),
+            UpdateStatus::Applied
+        );
+        assert_eq!(
+            book.apply_update(&update(105, 107, "[]", "[]")),
+            UpdateStatus::ResyncRequired {
+                last_update_id: 103,
+                first_update_id: 105,
+            }
+        );
+        assert_eq!(book.state(), SyncState::AwaitingSnapshot);
+        assert_eq!(
+            book.apply_update(&update(108, 110, "[]", "[]")),
+            UpdateStatus::Buffered
+        );
+    }
+
+    #[test]
+    fn first_update_must_bridge_snapshot() {
+        let mut book = BinanceBook::new(0.01);
+
+        book.apply_snapshot(&snapshot());
+
+        assert_eq!(
+            book.apply_update(&update(102, 105, "[]", "[]")),
+            UpdateStatus::ResyncRequired {
+                last_update_id: 100,
+                first_update_id: 102,
+            }
+        );
+    }
+
+    #[test]
+    fn futures_continuity() {
+        let mut book = BinanceBook::new(0.01);
+
+        book.apply_snapshot(&snapshot());
+
+        let mut first = update(95, 101, "[]", "[]");
+        first.previous_final_update_id = Some(94);
+        let mut second = update(104, 110, "[]", "[]");
+        second.previous_final_update_id = Some(101);
+        let mut third = update(111, 115, "[]", "[]");
+        third.previous_final_update_id = Some(109);
+
+        assert_eq!(book.apply_update(&first), UpdateStatus::Applied);
+        assert_eq!(book.apply_update(&second), UpdateStatus::Applied);
+        assert_eq!(
+            book.apply_update(&third),
+            UpdateStatus::ResyncRequired {
+                last_update_id: 110,
+                first_update_id: 111,
+            }
+        );
+    }
+
+    #[test]
+    fn futures_first_update_ending_at_snapshot() {
+        let mut book = BinanceBook::new(0.01);
+
+        book.apply_snapshot(&snapshot());
+
+        let mut stale = update(90, 99, "[]", "[]");
+        stale.previous_final_update_id = Some(89);
+        let mut first = update(95, 100, r#"[["0.0024","5"]]"#, "[]");
+        first.previous_final_update_id = Some(94);
+
+        assert_eq!(book.apply_update(&stale), UpdateStatus::Discarded);
+        assert_eq!(book.apply_update(&first), UpdateStatus::Applied);
+        assert_eq!(book.state(), SyncState::Synced);
+        assert_eq!(book.last_update_id(), 100);
+    }
+}
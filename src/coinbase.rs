@@ -0,0 +1,717 @@
+use crate::{
+    event::{deserialize_side, Event},
+    l3_orderbook::{Order, Orderbook as L3Orderbook},
+    orderbook::Orderbook,
+    time::parse_rfc3339,
+};
+use serde::de::{Deserializer, Error};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+fn deserialize_time<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = <&str>::deserialize(deserializer)?;
+    parse_rfc3339(value).ok_or_else(|| Error::custom(format!("invalid timestamp {:?}", value)))
+}
+
+fn deserialize_number<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = <&str>::deserialize(deserializer)?;
+    value
+        .parse()
+        .map_err(|_| Error::custom(format!("invalid number {:?}", value)))
+}
+
+fn deserialize_optional_number<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<&str>::deserialize(deserializer)? {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| Error::custom(format!("invalid number {:?}", value))),
+        None => Ok(None),
+    }
+}
+
+/// Message of the Advanced Trade `level2` channel. Messages of other channels, such as
+/// heartbeats and subscriptions, parse as well and are ignored by [`Level2Feed`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Level2Message {
+    pub channel: String,
+    /// Microseconds since epoch.
+    #[serde(deserialize_with = "deserialize_time")]
+    pub timestamp: u64,
+    pub sequence_num: u64,
+    #[serde(default)]
+    pub events: Vec<Level2Event>,
+}
+
+impl Level2Message {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Event of a `level2` message. Events of other channels parse with every field empty.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Level2Event {
+    /// `snapshot` or `update`.
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    #[serde(default)]
+    pub product_id: String,
+    #[serde(default)]
+    pub updates: Vec<Level2Update>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Level2Update {
+    #[serde(rename = "side", deserialize_with = "deserialize_side")]
+    pub is_buy: bool,
+    #[serde(rename = "price_level", deserialize_with = "deserialize_number")]
+    pub price: f64,
+    /// New absolute size of the level.
+    #[serde(rename = "new_quantity", deserialize_with = "deserialize_number")]
+    pub size: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceStatus {
+    /// The message was applied.
+    Applied,
+    /// The message carries nothing for the books, or its product has no snapshot yet.
+    Ignored,
+    /// The message is not newer than what was already applied and was discarded.
+    Stale,
+    /// Messages were missed and the product needs a new snapshot. Only snapshots carried by the
+    /// message were applied.
+    Gap { expected: u64, received: u64 },
+}
+
+/// Replays Advanced Trade `l2_data` recordings into one [`Orderbook`] per product.
+///
+/// `sequence_num` is shared by every product of a subscription, so a gap can't be attributed to
+/// one of them: every product is marked as out of sync until its next snapshot, which may come
+/// in the very message that opened the gap. Messages not newer than the last applied one are
+/// discarded.
+#[derive(Debug, Clone)]
+pub struct Level2Feed {
+    tick_size: f64,
+    books: HashMap<String, Level2Product>,
+    last_sequence: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+struct Level2Product {
+    book: Orderbook,
+    in_sync: bool,
+}
+
+impl Level2Feed {
+    pub fn new(tick_size: f64) -> Self {
+        Self {
+            tick_size,
+            books: HashMap::new(),
+            last_sequence: None,
+        }
+    }
+
+    pub fn book(&self, product_id: &str) -> Option<&Orderbook> {
+        self.books.get(product_id).map(|product| &product.book)
+    }
+
+    pub fn apply(&mut self, message: &Level2Message) -> SequenceStatus {
+        let mut gap = None;
+        if let Some(last) = self.last_sequence {
+            if message.sequence_num <= last {
+                return SequenceStatus::Stale;
+            }
+            if message.sequence_num != last + 1 {
+                self.books
+                    .values_mut()
+                    .for_each(|product| product.in_sync = false);
+                gap = Some(SequenceStatus::Gap {
+                    expected: last + 1,
+                    received: message.sequence_num,
+                });
+            }
+        }
+        self.last_sequence = Some(message.sequence_num);
+
+        if message.channel != "l2_data" {
+            return gap.unwrap_or(SequenceStatus::Ignored);
+        }
+
+        let mut status = SequenceStatus::Ignored;
+        for event in &message.events {
+            let is_snapshot = event.kind == "snapshot";
+
+            let product = self
+                .books
+                .entry(event.product_id.clone())
+                .or_insert_with(|| Level2Product {
+                    book: Orderbook::new(self.tick_size),
+                    in_sync: false,
+                });
+
+            if is_snapshot {
+                product.book.clear();
+                product.in_sync = true;
+            } else if !product.in_sync {
+                continue;
+            }
+
+            for update in &event.updates {
+                product.book.process(Event {
+                    timestamp: message.timestamp,
+                    seq: message.sequence_num,
+                    is_trade: false,
+                    is_buy: update.is_buy,
+                    price: update.price,
+                    size: update.size,
+                });
+            }
+
+            status = SequenceStatus::Applied;
+        }
+
+        gap.unwrap_or(status)
+    }
+}
+
+/// Message of the legacy Exchange `full` channel.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FullMessage {
+    Received {
+        product_id: String,
+        sequence: u64,
+        #[serde(deserialize_with = "deserialize_time")]
+        time: u64,
+        order_id: String,
+        #[serde(rename = "side", deserialize_with = "deserialize_side")]
+        is_buy: bool,
+    },
+    Open {
+        product_id: String,
+        sequence: u64,
+        #[serde(deserialize_with = "deserialize_time")]
+        time: u64,
+        order_id: String,
+        #[serde(rename = "side", deserialize_with = "deserialize_side")]
+        is_buy: bool,
+        #[serde(deserialize_with = "deserialize_number")]
+        price: f64,
+        #[serde(deserialize_with = "deserialize_number")]
+        remaining_size: f64,
+    },
+    Done {
+        product_id: String,
+        sequence: u64,
+        #[serde(deserialize_with = "deserialize_time")]
+        time: u64,
+        order_id: String,
+        #[serde(default)]
+        reason: String,
+    },
+    Match {
+        product_id: String,
+        sequence: u64,
+        #[serde(deserialize_with = "deserialize_time")]
+        time: u64,
+        maker_order_id: String,
+        taker_order_id: String,
+        /// Side of the maker order.
+        #[serde(rename = "side", deserialize_with = "deserialize_side")]
+        is_buy: bool,
+        #[serde(deserialize_with = "deserialize_number")]
+        price: f64,
+        #[serde(deserialize_with = "deserialize_number")]
+        size: f64,
+    },
+    Change {
+        product_id: String,
+        sequence: u64,
+        #[serde(deserialize_with = "deserialize_time")]
+        time: u64,
+        order_id: String,
+        #[serde(default, deserialize_with = "deserialize_optional_number")]
+        price: Option<f64>,
+        #[serde(default, deserialize_with = "deserialize_optional_number")]
+        new_price: Option<f64>,
+        #[serde(default, deserialize_with = "deserialize_optional_number")]
+        new_size: Option<f64>,
+    },
+    /// Any other message type, such as `activate` or `subscriptions`.
+    #[serde(other)]
+    Other,
+}
+
+impl FullMessage {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// Product and sequence of the message, `None` for [`FullMessage::Other`].
+    pub fn sequence(&self) -> Option<(&str, u64)> {
+        match self {
+            FullMessage::Received {
+                product_id,
+                sequence,
+                ..
+            }
+            | FullMessage::Open {
+                product_id,
+                sequence,
+                ..
+            }
+            | FullMessage::Done {
+                product_id,
+                sequence,
+                ..
+            }
+            | FullMessage::Match {
+                product_id,
+                sequence,
+                ..
+            }
+            | FullMessage::Change {
+                product_id,
+                sequence,
+                ..
+            } => Some((product_id, *sequence)),
+            FullMessage::Other => None,
+        }
+    }
+}
+
+/// Level 3 REST snapshot (`GET /products/<product_id>/book?level=3`), where every level is
+/// `[price, size, order_id]`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Level3Snapshot {
+    pub sequence: u64,
+    pub bids: Vec<(String, String, String)>,
+    pub asks: Vec<(String, String, String)>,
+}
+
+impl Level3Snapshot {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct FullProduct {
+    book: L3Orderbook,
+    last_sequence: u64,
+    in_sync: bool,
+    /// Order ids are UUIDs, the book is keyed by ids allocated in order of appearance.
+    ids: HashMap<String, u64>,
+    next_id: u64,
+}
+
+impl FullProduct {
+    fn new(tick_size: f64) -> Self {
+        Self {
+            book: L3Orderbook::new(tick_size),
+            last_sequence: 0,
+            in_sync: false,
+            ids: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    fn id(&mut self, order_id: &str) -> u64 {
+        if let Some(id) = self.ids.get(order_id) {
+            return *id;
+        }
+        self.next_id += 1;
+        self.ids.insert(order_id.to_string(), self.next_id);
+        self.next_id
+    }
+}
+
+/// Replays `full` channel recordings into one order-level book per product, returning the level
+/// 2 [`Event`]s of every change.
+///
+/// Sequences are checked per product: a gap marks the product as out of sync and its messages
+/// are ignored until a level 3 snapshot is applied. Messages of products without a snapshot are
+/// ignored as well, since sequences are only checked from one.
+#[derive(Debug, Clone)]
+pub struct FullFeed {
+    tick_size: f64,
+    products: HashMap<String, FullProduct>,
+}
+
+impl FullFeed {
+    pub fn new(tick_size: f64) -> Self {
+        Self {
+            tick_size,
+            products: HashMap::new(),
+        }
+    }
+
+    pub fn book(&self, product_id: &str) -> Option<&L3Orderbook> {
+        self.products.get(product_id).map(|product| &product.book)
+    }
+
+    /// Replaces the book of `product_id`, returning the level 2 events of the new book.
+    pub fn apply_snapshot(
+        &mut self,
+        product_id: &str,
+        snapshot: &Level3Snapshot,
+    ) -> serde_json::Result<Vec<Event>> {
+        let invalid = |v: &str| serde_json::Error::custom(format!("invalid number {:?}", v));
+
+        let mut orders = Vec::with_capacity(snapshot.bids.len() + snapshot.asks.len());
+        for (is_buy, levels) in [(true, &snapshot.bids), (false, &snapshot.asks)] {
+            for (price, size, order_id) in levels {
+                let price = price.parse::<f64>().map_err(|_| invalid(price))?;
+                let size = size.parse::<f64>().map_err(|_| invalid(size))?;
+                orders.push((order_id, is_buy, price, size));
+            }
+        }
+
+        let product = self.product(product_id);
+        product.book.clear();
+        product.ids.clear();
+        product.last_sequence = snapshot.sequence;
+        product.in_sync = true;
+
+        for (order_id, is_buy, price, size) in orders {
+            let order = Order::new(product.id(order_id), is_buy, price, size);
+            product.book.add(0, snapshot.sequence, order);
+        }
+
+        Ok(product.book.snapshot_events().collect())
+    }
+
+    /// Applies a message, returning its status and the level 2 events of the levels it changed.
+    pub fn apply(&mut self, message: &FullMessage) -> (SequenceStatus, Vec<Event>) {
+        let Some((product_id, sequence)) = message.sequence() else {
+            return (SequenceStatus::Ignored, Vec::new());
+        };
+
+        let Some(product) = self.products.get_mut(product_id) else {
+            return (SequenceStatus::Ignored, Vec::new());
+        };
+
+        if sequence <= product.last_sequence {
+            return (SequenceStatus::Stale, Vec::new());
+        }
+        if !product.in_sync {
+            return (SequenceStatus::Ignored, Vec::new());
+        }
+        if sequence != product.last_sequence + 1 {
+            let expected = product.last_sequence + 1;
+            product.in_sync = false;
+            product.last_sequence = sequence;
+            return (
+                SequenceStatus::Gap {
+                    expected,
+                    received: sequence,
+                },
+                Vec::new(),
+            );
+        }
+
+        product.last_sequence = sequence;
+
+        let mut events = Vec::new();
+        match message {
+            FullMessage::Open {
+                time,
+                order_id,
+                is_buy,
+                price,
+                remaining_size,
+                ..
+            } => {
+                let order = Order::new(product.id(order_id), *is_buy, *price, *remaining_size);
+                events.extend(product.book.add(*time, sequence, order));
+            }
+            FullMessage::Match {
+                time,
+                maker_order_id,
+                size,
+                ..
+            } => {
+                if let Some(&id) = product.ids.get(maker_order_id.as_str()) {
+                    events.extend(product.book.reduce(*time, sequence, id, *size));
+                }
+            }
+            FullMessage::Done { time, order_id, .. } => {
+                if let Some(id) = product.ids.remove(order_id.as_str()) {
+                    events.extend(product.book.delete(*time, sequence, id));
+                }
+            }
+            FullMessage::Change {
+                time,
+                order_id,
+                price,
+                new_price,
+                new_size,
+                ..
+            } => {
+                let order = product
+                    .ids
+                    .get(order_id.as_str())
+                    .and_then(|id| product.book.order(*id))
+                    .copied();
+                if let Some(order) = order {
+                    let price = new_price.or(*price).unwrap_or(order.price);
+                    let size = new_size.unwrap_or(order.size);
+                    let (removed, added) =
+                        product.book.modify(*time, sequence, order.id, price, size);
+                    events.extend(removed);
+                    events.extend(added);
+                }
+            }
+            // received orders are not on the book until they are open
+            FullMessage::Received { .. } | FullMessage::Other => {}
+        }
+
+        (SequenceStatus::Applied, events)
+    }
+
+    fn product(&mut self, product_id: &str) -> &mut FullProduct {
+        let tick_size = self.tick_size;
+        self.products
+            .entry(product_id.to_string())
+            .or_insert_with(|| FullProduct::new(tick_size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    fn l2(sequence_num: u64, kind: &str, product_id: &str, updates: &str) -> Level2Message {
+        Level2Message::from_json(&format!(
+            r#"{{"channel":"l2_data","client_id":"","timestamp":"2023-02-09T20:32:50.714964855Z","sequence_num":{},"events":[{{"type":"{}","product_id":"{}","updates":[{}]}}]}}"#,
+            sequence_num, kind, product_id, updates
+        ))
+        .unwrap()
+    }
+
+    fn update(side: &str, price: &str, size: &str) -> String {
+        format!(
+            r#"{{"side":"{}","event_time":"2023-02-09T20:32:50.714964855Z","price_level":"{}","new_quantity":"{}"}}"#,
+            side, price, size
+        )
+    }
+
+    #[test]
+    fn parse_level2() {
+        let message = l2(
+            3,
+            "snapshot",
+            "BTC-USD",
+            &[
+                update("bid", "21921.73", "0.06"),
+                update("offer", "21922.00", "1.5"),
+            ]
+            .join(","),
+        );
+
+        assert_eq!(message.timestamp, 1675974770714964);
+        assert_eq!(message.sequence_num, 3);
+        assert_eq!(message.events[0].product_id, "BTC-USD");
+        assert_eq!(
+            message.events[0].updates[1],
+            Level2Update {
+                is_buy: false,
+                price: 21922.0,
+                size: 1.5
+            }
+        );
+
+        let heartbeat = Level2Message::from_json(
+            r#"{"channel":"heartbeats","client_id":"","timestamp":"2023-06-23T20:31:26.122969572Z","sequence_num":4,"events":[{"current_time":"2023-06-23 20:31:56.121961769 +0000 UTC m=+91717.525857105","heartbeat_counter":"3049"}]}"#,
+        );
+
+        assert_eq!(heartbeat.unwrap().channel, "heartbeats");
+    }
+
+    #[test]
+    fn level2_feed() {
+        let mut feed = Level2Feed::new(0.01);
+
+        assert_eq!(
+            feed.apply(&l2(1, "update", "BTC-USD", &update("bid", "99", "1"))),
+            SequenceStatus::Ignored
+        );
+
+        let snapshot = [update("bid", "100", "1"), update("offer", "101", "2")].join(",");
+
+        assert_eq!(
+            feed.apply(&l2(2, "snapshot", "BTC-USD", &snapshot)),
+            SequenceStatus::Applied
+        );
+        assert_eq!(
+            feed.apply(&l2(3, "snapshot", "ETH-USD", &snapshot)),
+            SequenceStatus::Applied
+        );
+        assert_eq!(
+            feed.apply(&l2(4, "update", "BTC-USD", &update("bid", "100", "0"))),
+            SequenceStatus::Applied
+        );
+
+        assert_eq!(feed.book("BTC-USD").unwrap().best_bid(), None);
+        assert_eq!(
+            feed.book("ETH-USD").unwrap().best_bid(),
+            Some(Level::new(100.0, 1.0))
+        );
+
+        assert_eq!(
+            feed.apply(&l2(4, "update", "BTC-USD", &update("bid", "100", "3"))),
+            SequenceStatus::Stale
+        );
+        assert_eq!(
+            feed.apply(&l2(6, "update", "BTC-USD", &update("bid", "100", "3"))),
+            SequenceStatus::Gap {
+                expected: 5,
+                received: 6
+            }
+        );
+        assert_eq!(
+            feed.apply(&l2(7, "update", "ETH-USD", &update("bid", "100", "3"))),
+            SequenceStatus::Ignored
+        );
+        assert_eq!(
+            feed.apply(&l2(8, "snapshot", "ETH-USD", &update("bid", "98", "3"))),
+            SequenceStatus::Applied
+        );
+        assert_eq!(
+            feed.book("ETH-USD").unwrap().top_bids(5),
+            [Level::new(98.0, 3.0)]
+        );
+
+        // a snapshot opening a gap still resyncs its product
+        assert_eq!(
+            feed.apply(&l2(10, "snapshot", "ETH-USD", &update("bid", "97", "1"))),
+            SequenceStatus::Gap {
+                expected: 9,
+                received: 10
+            }
+        );
+        assert_eq!(
+            feed.apply(&l2(11, "update", "ETH-USD", &update("bid", "97", "2"))),
+            SequenceStatus::Applied
+        );
+        assert_eq!(
+            feed.book("ETH-USD").unwrap().top_bids(5),
+            [Level::new(97.0, 2.0)]
+        );
+        assert_eq!(
+            feed.apply(&l2(12, "update", "BTC-USD", &update("bid", "100", "3"))),
+            SequenceStatus::Ignored
+        );
+    }
+
+    fn full(json: &str) -> FullMessage {
+        FullMessage::from_json(json).unwrap()
+    }
+
+    #[test]
+    fn parse_full() {
+        assert_eq!(
+            full(
+                r#"{"type":"open","time":"2014-11-07T08:19:27.028459Z","product_id":"BTC-USD","sequence":10,"order_id":"d50ec984-77a8-460a-b958-66f114b0de9b","price":"200.2","remaining_size":"1.00","side":"sell"}"#
+            ),
+            FullMessage::Open {
+                product_id: "BTC-USD".to_string(),
+                sequence: 10,
+                time: 1415348367028459,
+                order_id: "d50ec984-77a8-460a-b958-66f114b0de9b".to_string(),
+                is_buy: false,
+                price: 200.2,
+                remaining_size: 1.0,
+            }
+        );
+
+        assert_eq!(
+            full(r#"{"type":"subscriptions","channels":[]}"#),
+            FullMessage::Other
+        );
+    }
+
+    #[test]
+    fn full_feed() {
+        let mut feed = FullFeed::new(0.01);
+
+        let (status, events) = feed.apply(&full(
+            r#"{"type":"open","time":"2014-11-07T08:19:27Z","product_id":"BTC-USD","sequence":9,"order_id":"e","price":"98","remaining_size":"1","side":"buy"}"#,
+        ));
+
+        assert_eq!(status, SequenceStatus::Ignored);
+        assert!(events.is_empty());
+        assert!(feed.book("BTC-USD").is_none());
+
+        let snapshot = Level3Snapshot::from_json(
+            r#"{"sequence":10,"bids":[["100.00","1.0","a"],["100.00","2.0","b"]],"asks":[["101.00","1.5","c"]]}"#,
+        )
+        .unwrap();
+
+        let events = feed.apply_snapshot("BTC-USD", &snapshot).unwrap();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].size, 3.0);
+
+        let (status, events) = feed.apply(&full(
+            r#"{"type":"open","time":"2014-11-07T08:19:27Z","product_id":"BTC-USD","sequence":10,"order_id":"d","price":"99","remaining_size":"1","side":"buy"}"#,
+        ));
+
+        assert_eq!(status, SequenceStatus::Stale);
+        assert!(events.is_empty());
+
+        let messages = [
+            r#"{"type":"received","time":"2014-11-07T08:19:27Z","product_id":"BTC-USD","sequence":11,"order_id":"d","size":"1","price":"99","side":"buy","order_type":"limit"}"#,
+            r#"{"type":"open","time":"2014-11-07T08:19:27Z","product_id":"BTC-USD","sequence":12,"order_id":"d","price":"99","remaining_size":"1","side":"buy"}"#,
+            r#"{"type":"match","trade_id":1,"time":"2014-11-07T08:19:28Z","product_id":"BTC-USD","sequence":13,"maker_order_id":"a","taker_order_id":"x","size":"0.5","price":"100.00","side":"buy"}"#,
+            r#"{"type":"change","reason":"modify_order","time":"2014-11-07T08:19:29Z","product_id":"BTC-USD","sequence":14,"order_id":"c","new_price":"100.50","new_size":"2.0","old_size":"1.5"}"#,
+            r#"{"type":"done","time":"2014-11-07T08:19:30Z","product_id":"BTC-USD","sequence":15,"order_id":"b","reason":"canceled","side":"buy","remaining_size":"2.0"}"#,
+        ];
+
+        let mut events = Vec::new();
+        for message in messages {
+            let (status, changed) = feed.apply(&full(message));
+            assert_eq!(status, SequenceStatus::Applied);
+            events.extend(changed);
+        }
+
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[1].size, 2.5);
+        assert_eq!(events[4].size, 0.5);
+
+        let book = feed.book("BTC-USD").unwrap();
+
+        assert_eq!(book.best_bid(), Some(Level::new(100.0, 0.5)));
+        assert_eq!(book.best_ask(), Some(Level::new(100.5, 2.0)));
+
+        let (status, _) = feed.apply(&full(
+            r#"{"type":"done","time":"2014-11-07T08:19:31Z","product_id":"BTC-USD","sequence":17,"order_id":"a","reason":"filled","side":"buy","remaining_size":"0"}"#,
+        ));
+
+        assert_eq!(
+            status,
+            SequenceStatus::Gap {
+                expected: 16,
+                received: 17
+            }
+        );
+
+        let (status, _) = feed.apply(&full(
+            r#"{"type":"done","time":"2014-11-07T08:19:31Z","product_id":"BTC-USD","sequence":18,"order_id":"d","reason":"filled","side":"buy","remaining_size":"0"}"#,
+        ));
+
+        assert_eq!(status, SequenceStatus::Ignored);
+    }
+}
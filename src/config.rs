@@ -0,0 +1,22 @@
+/// How the size of a level 2 event updates the level at its price.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateMode {
+    /// The size is the new size of the level, 0 removes it.
+    #[default]
+    Absolute,
+    /// The size is a signed change added to the level, which is removed when its size reaches 0
+    /// or below.
+    Delta,
+}
+
+/// Which side of the book a trade event depletes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TradeMode {
+    /// `is_buy` is the side of the resting order, so buys deplete the bids.
+    #[default]
+    Maker,
+    /// `is_buy` is the side of the taker, so buys deplete the asks.
+    Aggressor,
+    /// Trades leave the book untouched, for feeds whose level 2 updates already reflect them.
+    Ignore,
+}
@@ -0,0 +1,711 @@
+use crate::{
+    event::Event,
+    l3_orderbook::{Order, Orderbook as L3Orderbook},
+    level::Level,
+    orderbook::Orderbook,
+};
+use std::error;
+use std::fmt;
+use std::io::{self, Read};
+
+/// Prices in DBN are integers in units of 1e-9.
+const PRICE_SCALE: f64 = 1_000_000_000.0;
+/// Sentinel for a missing price.
+const UNDEF_PRICE: i64 = i64::MAX;
+
+const RTYPE_MBP_10: u8 = 0x0A;
+const RTYPE_MBO: u8 = 0xA0;
+
+/// Last record of an event, the book is consistent after applying it.
+pub const F_LAST: u8 = 1 << 7;
+/// Top of book message, not an individual order.
+pub const F_TOB: u8 = 1 << 6;
+/// Record sourced from a replay, such as a snapshot server.
+pub const F_SNAPSHOT: u8 = 1 << 5;
+/// Aggregated price level message, not an individual order.
+pub const F_MBP: u8 = 1 << 4;
+/// The `ts_recv` value is inaccurate.
+pub const F_BAD_TS_RECV: u8 = 1 << 3;
+/// An unrecoverable gap was detected in the channel.
+pub const F_MAYBE_BAD_BOOK: u8 = 1 << 2;
+
+/// Market by order record (`mbo` schema).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MboMsg {
+    pub publisher_id: u16,
+    pub instrument_id: u32,
+    pub ts_event: u64,
+    pub order_id: u64,
+    /// `None` when the price is undefined, as in clear book records.
+    pub price: Option<f64>,
+    pub size: u32,
+    pub flags: u8,
+    pub channel_id: u8,
+    /// `A`dd, `C`ancel, `M`odify, clea`R`, `T`rade, `F`ill or `N`one.
+    pub action: u8,
+    /// `A`sk, `B`id or `N`one.
+    pub side: u8,
+    pub ts_recv: u64,
+    pub ts_in_delta: i32,
+    pub sequence: u32,
+}
+
+/// Market by price record with 10 levels of depth (`mbp-10` schema).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mbp10Msg {
+    pub publisher_id: u16,
+    pub instrument_id: u32,
+    pub ts_event: u64,
+    pub price: Option<f64>,
+    pub size: u32,
+    pub action: u8,
+    pub side: u8,
+    pub flags: u8,
+    pub depth: u8,
+    pub ts_recv: u64,
+    pub ts_in_delta: i32,
+    pub sequence: u32,
+    /// Bid levels after the event, best first.
+    pub bids: Vec<Level>,
+    /// Ask levels after the event, best first.
+    pub asks: Vec<Level>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+    Mbo(MboMsg),
+    Mbp10(Mbp10Msg),
+    /// Any other record type, such as status or symbol mapping records.
+    Other {
+        rtype: u8,
+        instrument_id: u32,
+        ts_event: u64,
+    },
+}
+
+impl Record {
+    /// Parses a single record, including its header.
+    pub fn parse(buf: &[u8]) -> Result<Self, DbnError> {
+        if buf.len() < 16 {
+            return Err(DbnError::Truncated {
+                rtype: buf.get(1).copied().unwrap_or(0),
+                len: buf.len(),
+            });
+        }
+
+        let rtype = buf[1];
+        let publisher_id = le_u16(&buf[2..]);
+        let instrument_id = le_u32(&buf[4..]);
+        let ts_event = le_u64(&buf[8..]);
+
+        let expected = match rtype {
+            RTYPE_MBO => 56,
+            RTYPE_MBP_10 => 368,
+            _ => 16,
+        };
+
+        if buf.len() < expected {
+            return Err(DbnError::Truncated {
+                rtype,
+                len: buf.len(),
+            });
+        }
+
+        let record = match rtype {
+            RTYPE_MBO => Record::Mbo(MboMsg {
+                publisher_id,
+                instrument_id,
+                ts_event,
+                order_id: le_u64(&buf[16..]),
+                price: price(le_i64(&buf[24..])),
+                size: le_u32(&buf[32..]),
+                flags: buf[36],
+                channel_id: buf[37],
+                action: buf[38],
+                side: buf[39],
+                ts_recv: le_u64(&buf[40..]),
+                ts_in_delta: le_u32(&buf[48..]) as i32,
+                sequence: le_u32(&buf[52..]),
+            }),
+            RTYPE_MBP_10 => {
+                let mut bids = Vec::with_capacity(10);
+                let mut asks = Vec::with_capacity(10);
+
+                for level in buf[48..368].chunks_exact(32) {
+                    if let Some(bid_px) = price(le_i64(level)) {
+                        bids.push(Level::new(bid_px, le_u32(&level[16..]) as f64));
+                    }
+                    if let Some(ask_px) = price(le_i64(&level[8..])) {
+                        asks.push(Level::new(ask_px, le_u32(&level[20..]) as f64));
+                    }
+                }
+
+                Record::Mbp10(Mbp10Msg {
+                    publisher_id,
+                    instrument_id,
+                    ts_event,
+                    price: price(le_i64(&buf[16..])),
+                    size: le_u32(&buf[24..]),
+                    action: buf[28],
+                    side: buf[29],
+                    flags: buf[30],
+                    depth: buf[31],
+                    ts_recv: le_u64(&buf[32..]),
+                    ts_in_delta: le_u32(&buf[40..]) as i32,
+                    sequence: le_u32(&buf[44..]),
+                    bids,
+                    asks,
+                })
+            }
+            rtype => Record::Other {
+                rtype,
+                instrument_id,
+                ts_event,
+            },
+        };
+
+        Ok(record)
+    }
+}
+
+#[inline]
+fn le_u16(buf: &[u8]) -> u16 {
+    u16::from_le_bytes([buf[0], buf[1]])
+}
+
+#[inline]
+fn le_u32(buf: &[u8]) -> u32 {
+    u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])
+}
+
+#[inline]
+fn le_u64(buf: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&buf[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[inline]
+fn le_i64(buf: &[u8]) -> i64 {
+    le_u64(buf) as i64
+}
+
+#[inline]
+fn price(raw: i64) -> Option<f64> {
+    (raw != UNDEF_PRICE).then(|| raw as f64 / PRICE_SCALE)
+}
+
+/// Streaming reader of uncompressed DBN files. Files downloaded as `.dbn.zst` need to be
+/// decompressed first, either with `zstd -d` or by wrapping the file in a zstd decoder.
+#[derive(Debug)]
+pub struct DbnReader<R> {
+    reader: R,
+    version: u8,
+    buf: Vec<u8>,
+}
+
+impl<R: Read> DbnReader<R> {
+    /// Reads the metadata header, leaving the reader at the first record.
+    pub fn new(mut reader: R) -> Result<Self, DbnError> {
+        let mut prelude = [0; 8];
+        reader.read_exact(&mut prelude)?;
+
+        if &prelude[..3] != b"DBN" {
+            return Err(DbnError::InvalidHeader);
+        }
+
+        let version = prelude[3];
+        let len = le_u32(&prelude[4..]) as u64;
+
+        // the metadata itself (dataset, schema, symbology) is not needed to decode records
+        let skipped = io::copy(&mut reader.by_ref().take(len), &mut io::sink())?;
+        if skipped != len {
+            return Err(DbnError::InvalidHeader);
+        }
+
+        Ok(Self {
+            reader,
+            version,
+            buf: Vec::with_capacity(368),
+        })
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn read_record(&mut self) -> Result<Option<Record>, DbnError> {
+        let mut len = [0; 1];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+
+        // the length is given in 4 byte words and includes itself
+        let len = len[0] as usize * 4;
+        if len < 16 {
+            return Err(DbnError::Truncated { rtype: 0, len });
+        }
+
+        self.buf.clear();
+        self.buf.resize(len, 0);
+        self.buf[0] = (len / 4) as u8;
+        self.reader.read_exact(&mut self.buf[1..])?;
+
+        Record::parse(&self.buf).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for DbnReader<R> {
+    type Item = Result<Record, DbnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[derive(Debug)]
+pub enum DbnError {
+    Io(io::Error),
+    /// The stream doesn't start with a DBN metadata header.
+    InvalidHeader,
+    /// The record is shorter than its type requires.
+    Truncated {
+        rtype: u8,
+        len: usize,
+    },
+}
+
+impl fmt::Display for DbnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbnError::Io(err) => write!(f, "{}", err),
+            DbnError::InvalidHeader => write!(f, "invalid DBN metadata header"),
+            DbnError::Truncated { rtype, len } => {
+                write!(f, "record type {:#04x} truncated to {} bytes", rtype, len)
+            }
+        }
+    }
+}
+
+impl error::Error for DbnError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DbnError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DbnError {
+    fn from(value: io::Error) -> Self {
+        DbnError::Io(value)
+    }
+}
+
+/// Drives an order-level book from MBO records of a single instrument.
+///
+/// Records are held back until the one flagged with [`F_LAST`], then the whole batch is applied
+/// at once so the book is only ever observed in a consistent state. The level 2 [`Event`]s of the
+/// batch are timestamped with `ts_recv` in microseconds and sequenced by batch count. Trades and
+/// fills don't change the book, the cancels and modifies that follow them do.
+#[derive(Debug, Clone)]
+pub struct MboBook {
+    book: L3Orderbook,
+    instrument_id: Option<u32>,
+    pending: Vec<MboMsg>,
+    seq: u64,
+}
+
+impl MboBook {
+    /// Book for every record, when the file holds a single instrument.
+    pub fn new(tick_size: f64) -> Self {
+        Self {
+            book: L3Orderbook::new(tick_size),
+            instrument_id: None,
+            pending: Vec::new(),
+            seq: 0,
+        }
+    }
+
+    pub fn with_instrument(instrument_id: u32, tick_size: f64) -> Self {
+        Self {
+            instrument_id: Some(instrument_id),
+            ..Self::new(tick_size)
+        }
+    }
+
+    pub fn book(&self) -> &L3Orderbook {
+        &self.book
+    }
+
+    /// Adds a record to the current batch. Returns the level 2 events of the batch once its last
+    /// record is seen.
+    pub fn apply(&mut self, msg: &MboMsg) -> Option<Vec<Event>> {
+        if self.instrument_id.is_some_and(|id| id != msg.instrument_id) {
+            return None;
+        }
+
+        self.pending.push(*msg);
+
+        if msg.flags & F_LAST == 0 {
+            return None;
+        }
+
+        self.seq += 1;
+        let mut events = Vec::with_capacity(self.pending.len());
+
+        for msg in std::mem::take(&mut self.pending) {
+            let timestamp = msg.ts_recv / 1000;
+            let seq = self.seq;
+            let is_buy = match msg.side {
+                b'B' => true,
+                b'A' => false,
+                _ => {
+                    if msg.action == b'R' {
+                        let removed = self.book.snapshot_events().map(|event| Event {
+                            timestamp,
+                            seq,
+                            size: 0.0,
+                            ..event
+                        });
+                        events.extend(removed);
+                        self.book.clear();
+                    }
+                    continue;
+                }
+            };
+
+            match (msg.action, msg.price) {
+                (b'A', Some(price)) => {
+                    let order = Order::new(msg.order_id, is_buy, price, msg.size as f64);
+                    events.extend(self.book.add(timestamp, seq, order));
+                }
+                (b'C', _) => {
+                    events.extend(
+                        self.book
+                            .reduce(timestamp, seq, msg.order_id, msg.size as f64),
+                    );
+                }
+                (b'M', Some(price)) => {
+                    let (removed, added) = match self.book.order(msg.order_id) {
+                        Some(_) => {
+                            self.book
+                                .modify(timestamp, seq, msg.order_id, price, msg.size as f64)
+                        }
+                        // modifies of orders missed before the start of the data act as adds
+                        None => {
+                            let order = Order::new(msg.order_id, is_buy, price, msg.size as f64);
+                            (None, self.book.add(timestamp, seq, order))
+                        }
+                    };
+                    events.extend(removed);
+                    events.extend(added);
+                }
+                _ => {}
+            }
+        }
+
+        Some(events)
+    }
+}
+
+/// Keeps an [`Orderbook`] in sync with MBP-10 records of a single instrument.
+///
+/// Every record carries the top 10 levels after its event, so only the last record of a batch
+/// (flagged with [`F_LAST`]) is applied, by diffing its levels against the previous ones.
+#[derive(Debug, Clone)]
+pub struct Mbp10Book {
+    book: Orderbook,
+    instrument_id: Option<u32>,
+    bids: Vec<Level>,
+    asks: Vec<Level>,
+    seq: u64,
+}
+
+impl Mbp10Book {
+    pub fn new(tick_size: f64) -> Self {
+        Self::from_orderbook(Orderbook::new(tick_size))
+    }
+
+    pub fn with_instrument(instrument_id: u32, tick_size: f64) -> Self {
+        Self {
+            instrument_id: Some(instrument_id),
+            ..Self::new(tick_size)
+        }
+    }
+
+    pub fn from_orderbook(book: Orderbook) -> Self {
+        Self {
+            book,
+            instrument_id: None,
+            bids: Vec::new(),
+            asks: Vec::new(),
+            seq: 0,
+        }
+    }
+
+    pub fn book(&self) -> &Orderbook {
+        &self.book
+    }
+
+    pub fn into_inner(self) -> Orderbook {
+        self.book
+    }
+
+    /// Applies the record if it ends a batch, returning the events fed to the book.
+    pub fn apply(&mut self, msg: &Mbp10Msg) -> Option<Vec<Event>> {
+        if self.instrument_id.is_some_and(|id| id != msg.instrument_id) {
+            return None;
+        }
+
+        if msg.flags & F_LAST == 0 {
+            return None;
+        }
+
+        self.seq += 1;
+        let timestamp = msg.ts_recv / 1000;
+        let seq = self.seq;
+
+        let mut events = Vec::new();
+        for (is_buy, old, new) in [
+            (true, &self.bids, &msg.bids),
+            (false, &self.asks, &msg.asks),
+        ] {
+            let event = |level: &Level, size: f64| Event {
+                timestamp,
+                seq,
+                is_trade: false,
+                is_buy,
+                price: level.price,
+                size,
+            };
+
+            events.extend(
+                old.iter()
+                    .filter(|level| !new.iter().any(|l| l.price == level.price))
+                    .map(|level| event(level, 0.0)),
+            );
+            events.extend(
+                new.iter()
+                    .filter(|level| !old.contains(level))
+                    .map(|level| event(level, level.size)),
+            );
+        }
+
+        events.iter().for_each(|event| self.book.process(*event));
+
+        self.bids.clone_from(&msg.bids);
+        self.asks.clone_from(&msg.asks);
+
+        Some(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> Vec<u8> {
+        let mut buf = b"DBN\x02".to_vec();
+        buf.extend_from_slice(&4u32.to_le_bytes());
+        buf.extend_from_slice(&[0; 4]);
+        buf
+    }
+
+    fn header(len: usize, rtype: u8, instrument_id: u32) -> Vec<u8> {
+        let mut buf = vec![(len / 4) as u8, rtype];
+        buf.extend_from_slice(&1u16.to_le_bytes());
+        buf.extend_from_slice(&instrument_id.to_le_bytes());
+        buf.extend_from_slice(&1_000u64.to_le_bytes());
+        buf
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn mbo(
+        instrument_id: u32,
+        order_id: u64,
+        price: i64,
+        size: u32,
+        flags: u8,
+        action: u8,
+        side: u8,
+        ts_recv: u64,
+    ) -> Vec<u8> {
+        let mut buf = header(56, RTYPE_MBO, instrument_id);
+        buf.extend_from_slice(&order_id.to_le_bytes());
+        buf.extend_from_slice(&price.to_le_bytes());
+        buf.extend_from_slice(&size.to_le_bytes());
+        buf.extend_from_slice(&[flags, 0, action, side]);
+        buf.extend_from_slice(&ts_recv.to_le_bytes());
+        buf.extend_from_slice(&0i32.to_le_bytes());
+        buf.extend_from_slice(&7u32.to_le_bytes());
+        buf
+    }
+
+    fn mbp10(flags: u8, bids: &[(i64, u32)], asks: &[(i64, u32)]) -> Vec<u8> {
+        let mut buf = header(368, RTYPE_MBP_10, 1);
+        buf.extend_from_slice(&UNDEF_PRICE.to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.extend_from_slice(&[b'A', b'B', flags, 0]);
+        buf.extend_from_slice(&5_000_000u64.to_le_bytes());
+        buf.extend_from_slice(&0i32.to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        for i in 0..10 {
+            let (bid_px, bid_sz) = bids.get(i).copied().unwrap_or((UNDEF_PRICE, 0));
+            let (ask_px, ask_sz) = asks.get(i).copied().unwrap_or((UNDEF_PRICE, 0));
+            buf.extend_from_slice(&bid_px.to_le_bytes());
+            buf.extend_from_slice(&ask_px.to_le_bytes());
+            buf.extend_from_slice(&bid_sz.to_le_bytes());
+            buf.extend_from_slice(&ask_sz.to_le_bytes());
+            buf.extend_from_slice(&[0; 8]);
+        }
+        buf
+    }
+
+    const PX: i64 = 1_000_000_000;
+
+    #[test]
+    fn read_records() {
+        let mut data = metadata();
+        data.extend(mbo(1, 10, 100 * PX, 5, F_LAST, b'A', b'B', 2_000_000));
+        data.extend(header(16, 0x16, 1));
+        data.extend(mbp10(F_LAST, &[(99 * PX, 1)], &[(101 * PX, 2)]));
+
+        let mut reader = DbnReader::new(data.as_slice()).unwrap();
+
+        assert_eq!(reader.version(), 2);
+
+        let Record::Mbo(msg) = reader.next().unwrap().unwrap() else {
+            panic!("expected an mbo record");
+        };
+
+        assert_eq!(msg.order_id, 10);
+        assert_eq!(msg.price, Some(100.0));
+        assert_eq!(msg.size, 5);
+        assert_eq!(msg.action, b'A');
+        assert_eq!(msg.side, b'B');
+        assert_eq!(msg.ts_recv, 2_000_000);
+        assert_eq!(msg.sequence, 7);
+
+        assert_eq!(
+            reader.next().unwrap().unwrap(),
+            Record::Other {
+                rtype: 0x16,
+                instrument_id: 1,
+                ts_event: 1_000
+            }
+        );
+
+        let Record::Mbp10(msg) = reader.next().unwrap().unwrap() else {
+            panic!("expected an mbp-10 record");
+        };
+
+        assert_eq!(msg.price, None);
+        assert_eq!(msg.bids, [Level::new(99.0, 1.0)]);
+        assert_eq!(msg.asks, [Level::new(101.0, 2.0)]);
+
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert!(matches!(
+            DbnReader::new(&b"CSV\x02\x00\x00\x00\x00"[..]),
+            Err(DbnError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn mbo_batches() {
+        let mut data = metadata();
+        data.extend(mbo(1, 1, 100 * PX, 5, 0, b'A', b'B', 1_000_000));
+        data.extend(mbo(2, 9, 100 * PX, 5, F_LAST, b'A', b'B', 1_000_000));
+        data.extend(mbo(1, 2, 101 * PX, 3, F_LAST, b'A', b'A', 1_000_000));
+        data.extend(mbo(1, 2, 101 * PX, 3, 0, b'T', b'B', 2_000_000));
+        data.extend(mbo(1, 2, 101 * PX, 1, 0, b'F', b'A', 2_000_000));
+        data.extend(mbo(1, 2, 101 * PX, 1, 0, b'C', b'A', 2_000_000));
+        data.extend(mbo(1, 1, 99 * PX, 6, F_LAST, b'M', b'B', 2_000_000));
+        data.extend(mbo(1, 0, UNDEF_PRICE, 0, F_LAST, b'R', b'N', 3_000_000));
+
+        let mut mbo_book = MboBook::with_instrument(1, 0.01);
+        let mut batches = Vec::new();
+
+        for record in DbnReader::new(data.as_slice()).unwrap() {
+            let Record::Mbo(msg) = record.unwrap() else {
+                continue;
+            };
+
+            if let Some(events) = mbo_book.apply(&msg) {
+                if batches.is_empty() {
+                    assert_eq!(mbo_book.book().best_bid(), Some(Level::new(100.0, 5.0)));
+                    assert_eq!(mbo_book.book().best_ask(), Some(Level::new(101.0, 3.0)));
+                }
+                batches.push(events);
+            } else if !batches.is_empty() {
+                // records of an unfinished batch are not applied yet
+                assert_eq!(mbo_book.book().best_ask(), Some(Level::new(101.0, 3.0)));
+            }
+        }
+
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches[0].len(), 2);
+
+        assert_eq!(batches[1].len(), 3);
+        assert_eq!(batches[1][0].size, 2.0);
+        assert_eq!(batches[1][1].price, 100.0);
+        assert_eq!(batches[1][1].size, 0.0);
+        assert_eq!(batches[1][2].price, 99.0);
+        assert_eq!(batches[1][2].timestamp, 2_000);
+        assert_eq!(batches[1][2].seq, 2);
+
+        assert_eq!(batches[2].len(), 2);
+        assert!(batches[2].iter().all(|event| event.size == 0.0));
+        assert_eq!(mbo_book.book().best_bid(), None);
+    }
+
+    #[test]
+    fn mbp10_batches() {
+        let mut book = Mbp10Book::new(0.01);
+
+        let Record::Mbp10(first) = Record::parse(&mbp10(
+            F_LAST,
+            &[(100 * PX, 1), (99 * PX, 2)],
+            &[(101 * PX, 3)],
+        ))
+        .unwrap() else {
+            panic!("expected an mbp-10 record");
+        };
+
+        assert_eq!(book.apply(&first).unwrap().len(), 3);
+        assert_eq!(book.book().best_bid(), Some(Level::new(100.0, 1.0)));
+
+        let Record::Mbp10(pending) =
+            Record::parse(&mbp10(0, &[(99 * PX, 2)], &[(101 * PX, 3)])).unwrap()
+        else {
+            panic!("expected an mbp-10 record");
+        };
+
+        assert_eq!(book.apply(&pending), None);
+        assert_eq!(book.book().best_bid(), Some(Level::new(100.0, 1.0)));
+
+        let Record::Mbp10(last) = Record::parse(&mbp10(
+            F_LAST,
+            &[(99 * PX, 4)],
+            &[(101 * PX, 3), (102 * PX, 1)],
+        ))
+        .unwrap() else {
+            panic!("expected an mbp-10 record");
+        };
+
+        let events = book.apply(&last).unwrap();
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(book.book().top_bids(5), [Level::new(99.0, 4.0)]);
+        assert_eq!(
+            book.book().top_asks(5),
+            [Level::new(101.0, 3.0), Level::new(102.0, 1.0)]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use crate::orderbook::bps_bounds;
+
+/// Point of a depth curve, with the totals of the levels up to and including its price.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DepthPoint {
+    pub price: f64,
+    /// Cumulative base size.
+    pub size: f64,
+    /// Cumulative quote notional.
+    pub notional: f64,
+}
+
+/// Cumulative bid and ask depth out to a distance from the midprice, for depth charts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthCurve {
+    pub midprice: f64,
+    /// Bid points from the best price down.
+    pub bids: Vec<DepthPoint>,
+    /// Ask points from the best price up.
+    pub asks: Vec<DepthPoint>,
+}
+
+impl DepthCurve {
+    /// Bid totals within `bps` basis points below the midprice, so curves taken at different
+    /// prices can be compared at the same distances.
+    pub fn bid_depth_at_bps(&self, bps: f64) -> DepthPoint {
+        let (low, _) = bps_bounds(self.midprice, bps);
+        last_within(&self.bids, |price| price >= low)
+    }
+
+    /// Ask totals within `bps` basis points above the midprice.
+    pub fn ask_depth_at_bps(&self, bps: f64) -> DepthPoint {
+        let (_, high) = bps_bounds(self.midprice, bps);
+        last_within(&self.asks, |price| price <= high)
+    }
+}
+
+/// Last point whose price passes `within`, or an empty point at no price if there is none.
+fn last_within(points: &[DepthPoint], within: impl Fn(f64) -> bool) -> DepthPoint {
+    points
+        .iter()
+        .take_while(|point| within(point.price))
+        .last()
+        .copied()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{event::Event, orderbook::Orderbook};
+
+    #[test]
+    fn depth_curve() {
+        let mut ob = Orderbook::new(0.01);
+
+        [
+            (true, 99.0, 1.0),
+            (true, 98.0, 2.0),
+            (true, 97.0, 3.0),
+            (false, 101.0, 1.0),
+            (false, 102.0, 2.0),
+            (false, 110.0, 5.0),
+        ]
+        .into_iter()
+        .enumerate()
+        .for_each(|(i, (is_buy, price, size))| {
+            ob.process(Event {
+                timestamp: 0,
+                seq: i as u64,
+                is_trade: false,
+                is_buy,
+                price,
+                size,
+            })
+        });
+
+        let curve = ob.depth_curve(250.0).unwrap();
+
+        assert_eq!(curve.midprice, 100.0);
+        assert_eq!(
+            curve.bids,
+            [
+                DepthPoint {
+                    price: 99.0,
+                    size: 1.0,
+                    notional: 99.0
+                },
+                DepthPoint {
+                    price: 98.0,
+                    size: 3.0,
+                    notional: 295.0
+                },
+            ]
+        );
+        assert_eq!(curve.asks.len(), 2);
+        assert_eq!(curve.asks[1].notional, 305.0);
+
+        assert_eq!(curve.bid_depth_at_bps(150.0).size, 1.0);
+        assert_eq!(curve.ask_depth_at_bps(250.0).size, 3.0);
+        assert_eq!(curve.ask_depth_at_bps(50.0), DepthPoint::default());
+        assert_eq!(
+            curve.bid_depth_at_bps(250.0).size,
+            ob.bid_depth_within_bps(250.0).unwrap()
+        );
+        assert_eq!(
+            curve
+                .asks
+                .iter()
+                .map(|point| point.size)
+                .collect::<Vec<_>>(),
+            ob.cumulative_asks()
+                .take(2)
+                .map(|level| level.size)
+                .collect::<Vec<_>>()
+        );
+        assert!(Orderbook::new(0.01).depth_curve(100.0).is_none());
+    }
+}
@@ -1,11 +1,13 @@
+use serde::de::DeserializeSeed;
 use serde::de::Deserializer;
 use serde::de::Error;
 use serde::de::MapAccess;
+use serde::de::Unexpected;
 use serde::de::Visitor;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub struct Event {
     pub timestamp: u64,
     pub seq: u64,
@@ -21,6 +23,94 @@ impl Event {
     }
 }
 
+/// Parses a boolean-like value: `1`/`0`, `true`/`false`, `t`/`f`, `yes`/`no`, `y`/`n` (case
+/// insensitive).
+pub fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "t" | "yes" | "y" => Some(true),
+        "0" | "false" | "f" | "no" | "n" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a side value, returning `true` for the bid/buy side and `false` for the ask/sell side.
+/// Accepts `buy`/`sell`, `bid`/`ask`/`offer`, `b`/`s`/`a`, `1`/`0` and `true`/`false` (case
+/// insensitive).
+pub fn parse_side(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "buy" | "bid" | "b" | "1" | "true" => Some(true),
+        "sell" | "ask" | "offer" | "s" | "a" | "0" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Deserializes a boolean field that may be written as a number, a boolean or a string,
+/// reporting the offending value when it can't be interpreted.
+struct FlagSeed(fn(&str) -> Option<bool>);
+
+impl<'de> DeserializeSeed<'de> for FlagSeed {
+    type Value = bool;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FlagSeed {
+    type Value = bool;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("0/1, true/false or a side such as buy/sell/bid/ask")
+    }
+
+    fn visit_bool<E: Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+        match v {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::invalid_value(Unexpected::Unsigned(v), &self)),
+        }
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Self::Value, E> {
+        match v {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::invalid_value(Unexpected::Signed(v), &self)),
+        }
+    }
+
+    fn visit_f64<E: Error>(self, v: f64) -> Result<Self::Value, E> {
+        Err(Error::invalid_value(Unexpected::Float(v), &self))
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        (self.0)(v).ok_or_else(|| Error::invalid_value(Unexpected::Str(v), &self))
+    }
+}
+
+/// `deserialize_with` helper for fields parsed with [`parse_flag`].
+pub(crate) fn deserialize_flag<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    FlagSeed(parse_flag).deserialize(deserializer)
+}
+
+/// `deserialize_with` helper for fields parsed with [`parse_side`].
+pub(crate) fn deserialize_side<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    FlagSeed(parse_side).deserialize(deserializer)
+}
+
 impl<'de> Deserialize<'de> for Event {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -43,6 +133,7 @@ impl<'de> Deserialize<'de> for Event {
                 let mut seq = None;
                 let mut is_trade = None;
                 let mut is_buy = None;
+                let mut side_field = None;
                 let mut price = None;
                 let mut size = None;
 
@@ -64,21 +155,25 @@ impl<'de> Deserialize<'de> for Event {
                             if is_trade.is_some() {
                                 return Err(Error::duplicate_field("is_trade"));
                             }
-                            is_trade = match map.next_value()? {
-                                0 => Some(false),
-                                1 => Some(true),
-                                _ => None,
-                            }
+                            is_trade = Some(map.next_value_seed(FlagSeed(parse_flag))?);
                         }
-                        "is_buy" => {
-                            if is_buy.is_some() {
-                                return Err(Error::duplicate_field("is_buy"));
-                            }
-                            is_buy = match map.next_value()? {
-                                0 => Some(false),
-                                1 => Some(true),
-                                _ => None,
+                        "is_buy" | "side" => {
+                            let field = match key {
+                                "side" => "side",
+                                _ => "is_buy",
+                            };
+                            match side_field {
+                                Some(previous) if previous == field => {
+                                    return Err(Error::duplicate_field(field));
+                                }
+                                Some(_) => {
+                                    return Err(Error::custom(
+                                        "both `is_buy` and `side` are present, expected only one",
+                                    ));
+                                }
+                                None => side_field = Some(field),
                             }
+                            is_buy = Some(map.next_value_seed(FlagSeed(parse_side))?);
                         }
                         "price" => {
                             if price.is_some() {
@@ -142,6 +237,75 @@ mod tests {
         assert_eq!(event.size, 0.085806);
     }
 
+    #[test]
+    fn deser_text_flags() {
+        let data =
+            "timestamp,seq,is_trade,side,price,size\n1,0,true,ask,10.0,1.0\n2,1,false,buy,9.0,1.0";
+
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let events = reader
+            .deserialize::<Event>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert!(events[0].is_trade);
+        assert!(!events[0].is_buy);
+        assert!(!events[1].is_trade);
+        assert!(events[1].is_buy);
+    }
+
+    #[test]
+    fn deser_invalid_flag() {
+        let data = "timestamp,seq,is_trade,is_buy,price,size\n1,0,0,2,10.0,1.0";
+
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let err = reader.deserialize::<Event>().next().unwrap().unwrap_err();
+
+        let message = err.to_string();
+        assert!(message.contains("line: 2"), "{}", message);
+        assert!(
+            message.contains("invalid value: integer `2`"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn deser_both_side_columns() {
+        let data = "timestamp,seq,is_trade,is_buy,side,price,size\n1,0,0,1,buy,10.0,1.0";
+
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let err = reader.deserialize::<Event>().next().unwrap().unwrap_err();
+
+        let message = err.to_string();
+        assert!(
+            message.contains("both `is_buy` and `side` are present"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn parse_flags_and_sides() {
+        for value in ["1", "true", "t", "yes", "y", "True", " YES "] {
+            assert_eq!(parse_flag(value), Some(true), "{}", value);
+        }
+        for value in ["0", "false", "f", "no", "n", "False"] {
+            assert_eq!(parse_flag(value), Some(false), "{}", value);
+        }
+        assert_eq!(parse_flag("buy"), None);
+
+        for value in ["buy", "bid", "b", "1", "true", "TRUE"] {
+            assert_eq!(parse_side(value), Some(true), "{}", value);
+        }
+        for value in ["sell", "ask", "offer", "s", "a", "0", "false", "Sell"] {
+            assert_eq!(parse_side(value), Some(false), "{}", value);
+        }
+        for value in ["maybe", "yes", "y", "t", "no", "n", "f"] {
+            assert_eq!(parse_side(value), None, "{}", value);
+        }
+    }
+
     #[test]
     fn price_ticks() {
         let event = Event {
@@ -0,0 +1,527 @@
+use crate::{
+    config::TradeMode, event::Event, level::Bbo, orderbook::Orderbook, time::parse_fix_timestamp,
+};
+use std::error;
+use std::fmt;
+
+const MSG_SEQ_NUM: u32 = 34;
+const MSG_TYPE: u32 = 35;
+const SIDE: u32 = 54;
+const SENDING_TIME: u32 = 52;
+const SYMBOL: u32 = 55;
+const NO_MD_ENTRIES: u32 = 268;
+const MD_ENTRY_TYPE: u32 = 269;
+const MD_ENTRY_PX: u32 = 270;
+const MD_ENTRY_SIZE: u32 = 271;
+const MD_UPDATE_ACTION: u32 = 279;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    Bid,
+    Offer,
+    Trade,
+    /// Any other `MDEntryType`, ignored by the book.
+    Other(char),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateAction {
+    New,
+    Change,
+    Delete,
+}
+
+/// Repeating group entry of a market data message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub action: UpdateAction,
+    /// `None` when the entry has no `MDEntryType` (269).
+    pub entry_type: Option<EntryType>,
+    pub symbol: Option<String>,
+    pub price: Option<f64>,
+    pub size: Option<f64>,
+    /// `Side` (54), reported by some venues on trade entries as the side of the aggressor.
+    pub is_buy: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    /// `35=W`
+    Snapshot,
+    /// `35=X`
+    Incremental,
+}
+
+/// FIX 4.4 market data message (`35=W` full refresh or `35=X` incremental refresh).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketDataMessage {
+    pub msg_type: MessageType,
+    pub seq: u64,
+    /// `SendingTime` (52) in microseconds since epoch.
+    pub sending_time: u64,
+    /// Message level `Symbol` (55), set on full refreshes.
+    pub symbol: Option<String>,
+    pub entries: Vec<Entry>,
+}
+
+impl MarketDataMessage {
+    /// Parses a tag=value message separated by SOH, or by `|` as found in many logs.
+    /// Messages other than `35=W` and `35=X` are rejected with [`FixError::UnsupportedMsgType`].
+    pub fn parse(message: &str) -> Result<Self, FixError> {
+        let separator = if message.contains('\x01') {
+            '\x01'
+        } else {
+            '|'
+        };
+
+        let mut fields = Vec::new();
+        for field in message.split(separator).filter(|f| !f.is_empty()) {
+            let (tag, value) = field
+                .split_once('=')
+                .ok_or_else(|| FixError::MalformedField(field.to_string()))?;
+            let tag = tag
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| FixError::MalformedField(field.to_string()))?;
+            fields.push((tag, value));
+        }
+
+        let value = |tag: u32| fields.iter().find(|(t, _)| *t == tag).map(|(_, v)| *v);
+
+        let msg_type = match value(MSG_TYPE).ok_or(FixError::MissingTag(MSG_TYPE))? {
+            "W" => MessageType::Snapshot,
+            "X" => MessageType::Incremental,
+            other => return Err(FixError::UnsupportedMsgType(other.to_string())),
+        };
+
+        let seq = value(MSG_SEQ_NUM)
+            .ok_or(FixError::MissingTag(MSG_SEQ_NUM))
+            .and_then(|v| v.parse().map_err(|_| invalid(MSG_SEQ_NUM, v)))?;
+
+        let sending_time = value(SENDING_TIME)
+            .ok_or(FixError::MissingTag(SENDING_TIME))
+            .and_then(|v| parse_fix_timestamp(v).ok_or_else(|| invalid(SENDING_TIME, v)))?;
+
+        let group_start = fields
+            .iter()
+            .position(|(tag, _)| *tag == NO_MD_ENTRIES)
+            .ok_or(FixError::MissingTag(NO_MD_ENTRIES))?;
+
+        let count = fields[group_start]
+            .1
+            .parse::<usize>()
+            .map_err(|_| invalid(NO_MD_ENTRIES, fields[group_start].1))?;
+
+        // full refreshes carry the symbol before the group
+        let symbol = fields[..group_start]
+            .iter()
+            .find(|(tag, _)| *tag == SYMBOL)
+            .map(|(_, v)| v.to_string());
+
+        // every entry starts with the first field of the group
+        let delimiter = match msg_type {
+            MessageType::Snapshot => MD_ENTRY_TYPE,
+            MessageType::Incremental => MD_UPDATE_ACTION,
+        };
+
+        let mut entries: Vec<Entry> = Vec::with_capacity(count);
+        for &(tag, value) in &fields[group_start + 1..] {
+            if tag == delimiter {
+                entries.push(Entry {
+                    action: UpdateAction::New,
+                    entry_type: None,
+                    symbol: None,
+                    price: None,
+                    size: None,
+                    is_buy: None,
+                });
+            }
+
+            let Some(entry) = entries.last_mut() else {
+                continue;
+            };
+
+            match tag {
+                MD_UPDATE_ACTION => {
+                    entry.action = match value {
+                        "0" => UpdateAction::New,
+                        "1" => UpdateAction::Change,
+                        "2" => UpdateAction::Delete,
+                        _ => return Err(invalid(tag, value)),
+                    }
+                }
+                MD_ENTRY_TYPE => {
+                    entry.entry_type = match value {
+                        "0" => Some(EntryType::Bid),
+                        "1" => Some(EntryType::Offer),
+                        "2" => Some(EntryType::Trade),
+                        _ => match value.chars().next() {
+                            Some(other) => Some(EntryType::Other(other)),
+                            None => return Err(invalid(tag, value)),
+                        },
+                    }
+                }
+                MD_ENTRY_PX => entry.price = Some(value.parse().map_err(|_| invalid(tag, value))?),
+                MD_ENTRY_SIZE => entry.size = Some(value.parse().map_err(|_| invalid(tag, value))?),
+                SYMBOL => entry.symbol = Some(value.to_string()),
+                SIDE => {
+                    entry.is_buy = match value {
+                        "1" => Some(true),
+                        "2" => Some(false),
+                        _ => None,
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if entries.len() != count {
+            return Err(FixError::EntryCount {
+                expected: count,
+                found: entries.len(),
+            });
+        }
+
+        Ok(Self {
+            msg_type,
+            seq,
+            sending_time,
+            symbol,
+            entries,
+        })
+    }
+
+    /// Events of the message for `symbol` (every entry when `None`), sequenced by `MsgSeqNum`.
+    ///
+    /// Entries without a `Symbol` (55) of their own or on the message match any `symbol`, as
+    /// single symbol sessions often leave it out. On streams carrying several symbols every entry
+    /// must have one, or it is applied to the book of each of them.
+    ///
+    /// The whole message is validated first, so either every entry is converted or an error is
+    /// returned. Entries without a type are an error, while trade entries without a side and
+    /// entries of types other than bid, offer and trade are left out.
+    pub fn events(&self, symbol: Option<&str>) -> Result<Vec<Event>, FixError> {
+        let mut events = Vec::with_capacity(self.entries.len());
+
+        for (index, entry) in self.entries.iter().enumerate() {
+            let entry_symbol = entry.symbol.as_deref().or(self.symbol.as_deref());
+            if symbol.is_some() && entry_symbol.is_some() && entry_symbol != symbol {
+                continue;
+            }
+
+            let entry_type = entry.entry_type.ok_or(FixError::IncompleteEntry {
+                index,
+                tag: MD_ENTRY_TYPE,
+            })?;
+
+            let (is_trade, is_buy) = match (entry_type, entry.is_buy) {
+                (EntryType::Bid, _) => (false, true),
+                (EntryType::Offer, _) => (false, false),
+                (EntryType::Trade, Some(is_buy)) => (true, is_buy),
+                (EntryType::Trade, None) | (EntryType::Other(_), _) => continue,
+            };
+
+            let price = entry.price.ok_or(FixError::IncompleteEntry {
+                index,
+                tag: MD_ENTRY_PX,
+            })?;
+
+            let size = match (entry.action, entry.size) {
+                (UpdateAction::Delete, _) if !is_trade => 0.0,
+                (_, Some(size)) => size,
+                (_, None) => {
+                    return Err(FixError::IncompleteEntry {
+                        index,
+                        tag: MD_ENTRY_SIZE,
+                    })
+                }
+            };
+
+            events.push(Event {
+                timestamp: self.sending_time,
+                seq: self.seq,
+                is_trade,
+                is_buy,
+                price,
+                size,
+            });
+        }
+
+        Ok(events)
+    }
+}
+
+fn invalid(tag: u32, value: &str) -> FixError {
+    FixError::InvalidValue {
+        tag,
+        value: value.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixError {
+    /// A field without `=` or with a non numeric tag.
+    MalformedField(String),
+    MissingTag(u32),
+    InvalidValue {
+        tag: u32,
+        value: String,
+    },
+    UnsupportedMsgType(String),
+    /// `NoMDEntries` doesn't match the number of entries in the message.
+    EntryCount {
+        expected: usize,
+        found: usize,
+    },
+    /// An entry misses a tag required to apply it to the book.
+    IncompleteEntry {
+        index: usize,
+        tag: u32,
+    },
+}
+
+impl fmt::Display for FixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixError::MalformedField(field) => write!(f, "malformed field {:?}", field),
+            FixError::MissingTag(tag) => write!(f, "missing tag {}", tag),
+            FixError::InvalidValue { tag, value } => {
+                write!(f, "invalid value {:?} for tag {}", value, tag)
+            }
+            FixError::UnsupportedMsgType(msg_type) => {
+                write!(f, "unsupported message type {:?}", msg_type)
+            }
+            FixError::EntryCount { expected, found } => {
+                write!(f, "expected {} entries, found {}", expected, found)
+            }
+            FixError::IncompleteEntry { index, tag } => {
+                write!(f, "entry {} is missing tag {}", index, tag)
+            }
+        }
+    }
+}
+
+impl error::Error for FixError {}
+
+/// Applies FIX market data messages for a single symbol to an [`Orderbook`].
+///
+/// Full refreshes replace the book and incremental refreshes apply all of their entries, each
+/// message being applied entirely or not at all. Entries without a symbol are applied as well,
+/// see [`MarketDataMessage::events`].
+#[derive(Debug, Clone)]
+pub struct FixBook {
+    book: Orderbook,
+    symbol: String,
+}
+
+impl FixBook {
+    /// Trades reduce the side opposite to their `Side` (54), which is the aggressor's side.
+    pub fn new(symbol: &str, tick_size: f64) -> Self {
+        Self::from_orderbook(
+            symbol,
+            Orderbook::new(tick_size).with_trade_mode(TradeMode::Aggressor),
+        )
+    }
+
+    /// Uses `book` as is, so its [`TradeMode`] should match the meaning of `Side` (54) on the
+    /// venue.
+    pub fn from_orderbook(symbol: &str, book: Orderbook) -> Self {
+        Self {
+            book,
+            symbol: symbol.to_string(),
+        }
+    }
+
+    pub fn book(&self) -> &Orderbook {
+        &self.book
+    }
+
+    pub fn into_inner(self) -> Orderbook {
+        self.book
+    }
+
+    /// Applies a message, returning the new best bid and ask if the message changed them.
+    /// Messages for other symbols are ignored.
+    pub fn apply(&mut self, message: &MarketDataMessage) -> Result<Option<Bbo>, FixError> {
+        if message.msg_type == MessageType::Snapshot
+            && message.symbol.as_deref().is_some_and(|s| s != self.symbol)
+        {
+            return Ok(None);
+        }
+
+        let events = message.events(Some(&self.symbol))?;
+
+        let old_bid = self.book.best_bid();
+        let old_ask = self.book.best_ask();
+
+        if message.msg_type == MessageType::Snapshot {
+            self.book.clear();
+        }
+
+        events
+            .into_iter()
+            .for_each(|event| self.book.process(event));
+
+        let new_bid = self.book.best_bid();
+        let new_ask = self.book.best_ask();
+
+        if old_bid != new_bid || old_ask != new_ask {
+            Ok(Some((new_bid, new_ask)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn apply_str(&mut self, message: &str) -> Result<Option<Bbo>, FixError> {
+        self.apply(&MarketDataMessage::parse(message)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    const SNAPSHOT: &str = "8=FIX.4.4|9=200|35=W|34=10|49=VENUE|56=CLIENT|52=20240101-00:00:00.000|55=BTC-USD|268=3|269=0|270=100.0|271=1.5|269=1|270=101.0|271=2|269=1|270=102.0|271=3|10=000|";
+
+    #[test]
+    fn parse_snapshot() {
+        let message = MarketDataMessage::parse(SNAPSHOT).unwrap();
+
+        assert_eq!(message.msg_type, MessageType::Snapshot);
+        assert_eq!(message.seq, 10);
+        assert_eq!(message.sending_time, 1704067200000000);
+        assert_eq!(message.symbol.as_deref(), Some("BTC-USD"));
+        assert_eq!(message.entries.len(), 3);
+        assert_eq!(message.entries[0].entry_type, Some(EntryType::Bid));
+        assert_eq!(message.entries[2].price, Some(102.0));
+
+        let soh = SNAPSHOT.replace('|', "\x01");
+
+        assert_eq!(MarketDataMessage::parse(&soh).unwrap(), message);
+    }
+
+    #[test]
+    fn parse_incremental() {
+        let message = MarketDataMessage::parse(
+            "8=FIX.4.4|35=X|34=11|52=20240101-00:00:01.500|268=3|279=0|269=0|55=BTC-USD|270=100.5|271=1|279=2|269=1|55=BTC-USD|270=101.0|279=0|269=2|55=BTC-USD|270=101.0|271=0.5|54=1|10=000|",
+        )
+        .unwrap();
+
+        assert_eq!(message.msg_type, MessageType::Incremental);
+        assert_eq!(message.entries[1].action, UpdateAction::Delete);
+        assert_eq!(message.entries[1].size, None);
+        assert_eq!(message.entries[2].is_buy, Some(true));
+
+        let events = message.events(Some("BTC-USD")).unwrap();
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].timestamp, 1704067201500000);
+        assert!(events[0].is_buy);
+        assert_eq!(events[1].size, 0.0);
+        assert!(events[2].is_trade);
+
+        assert!(message.events(Some("ETH-USD")).unwrap().is_empty());
+
+        let message = MarketDataMessage::parse(
+            "35=X|34=12|52=20240101-00:00:02|268=2|279=0|269=0|55=BTC-USD|270=100.5|271=1|279=0|55=BTC-USD|270=101.0|271=1|",
+        )
+        .unwrap();
+
+        assert_eq!(message.entries[1].entry_type, None);
+
+        // entries without a symbol match any filter
+        let anonymous = MarketDataMessage::parse(
+            "35=X|34=13|52=20240101-00:00:03|268=1|279=0|269=0|270=100.5|271=1|",
+        )
+        .unwrap();
+
+        assert_eq!(anonymous.events(Some("ETH-USD")).unwrap().len(), 1);
+        assert_eq!(
+            message.events(Some("BTC-USD")).unwrap_err(),
+            FixError::IncompleteEntry { index: 1, tag: 269 }
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            MarketDataMessage::parse("35=D|34=1|52=20240101-00:00:00|").unwrap_err(),
+            FixError::UnsupportedMsgType("D".to_string())
+        );
+        assert_eq!(
+            MarketDataMessage::parse(
+                "35=X|34=1|52=20240101-00:00:00|268=2|279=0|269=0|270=1|271=1|"
+            )
+            .unwrap_err(),
+            FixError::EntryCount {
+                expected: 2,
+                found: 1
+            }
+        );
+        assert_eq!(
+            MarketDataMessage::parse("35=X|34=1|52=yesterday|268=0|").unwrap_err(),
+            FixError::InvalidValue {
+                tag: 52,
+                value: "yesterday".to_string()
+            }
+        );
+        assert_eq!(
+            MarketDataMessage::parse("35=X|34=1|52=202é010-00:00:00|268=0|").unwrap_err(),
+            FixError::InvalidValue {
+                tag: 52,
+                value: "202é010-00:00:00".to_string()
+            }
+        );
+        assert_eq!(
+            MarketDataMessage::parse("35=X|34|").unwrap_err(),
+            FixError::MalformedField("34".to_string())
+        );
+    }
+
+    #[test]
+    fn apply_messages() {
+        let mut fix = FixBook::new("BTC-USD", 0.5);
+
+        let bbo = fix.apply_str(SNAPSHOT).unwrap();
+
+        assert_eq!(
+            bbo,
+            Some((Some(Level::new(100.0, 1.5)), Some(Level::new(101.0, 2.0))))
+        );
+
+        let bbo = fix
+            .apply_str("35=X|34=11|52=20240101-00:00:01|268=2|279=2|269=1|55=BTC-USD|270=101.0|279=1|269=0|55=BTC-USD|270=100.0|271=4|")
+            .unwrap();
+
+        assert_eq!(
+            bbo,
+            Some((Some(Level::new(100.0, 4.0)), Some(Level::new(102.0, 3.0))))
+        );
+
+        // the second entry is missing its size so nothing is applied
+        let err = fix
+            .apply_str("35=X|34=12|52=20240101-00:00:02|268=2|279=0|269=0|55=BTC-USD|270=101.0|271=1|279=0|269=1|55=BTC-USD|270=101.5|")
+            .unwrap_err();
+
+        assert_eq!(err, FixError::IncompleteEntry { index: 1, tag: 271 });
+        assert_eq!(fix.book().best_bid(), Some(Level::new(100.0, 4.0)));
+
+        // a buy aggressor takes from the asks
+        let bbo = fix
+            .apply_str("35=X|34=12|52=20240101-00:00:02|268=1|279=0|269=2|55=BTC-USD|270=102.0|271=1|54=1|")
+            .unwrap();
+
+        assert_eq!(
+            bbo,
+            Some((Some(Level::new(100.0, 4.0)), Some(Level::new(102.0, 2.0))))
+        );
+
+        let bbo = fix
+            .apply_str("35=W|34=13|52=20240101-00:00:03|55=BTC-USD|268=1|269=0|270=99.0|271=1|")
+            .unwrap();
+
+        assert_eq!(bbo, Some((Some(Level::new(99.0, 1.0)), None)));
+        assert_eq!(fix.book().top_asks(5), []);
+    }
+}
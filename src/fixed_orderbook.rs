@@ -1,6 +1,10 @@
 use std::{hint::unreachable_unchecked, mem::replace};
 
-use crate::{event::Event, level::Level};
+use crate::{
+    config::{TradeMode, UpdateMode},
+    event::Event,
+    level::Level,
+};
 
 /// Implementation of an orderbook with fixed size to use as a benchmark
 /// This has no use other than benchmarking.
@@ -12,6 +16,9 @@ pub struct Orderbook {
     asks: Buffer,
     last_updated: u64,
     last_sequence: u64,
+    update_mode: UpdateMode,
+    trade_mode: TradeMode,
+    sweep: bool,
 }
 
 impl Default for Orderbook {
@@ -29,9 +36,28 @@ impl Orderbook {
             asks: Buffer::new(false),
             last_updated: 0,
             last_sequence: 0,
+            update_mode: UpdateMode::Absolute,
+            trade_mode: TradeMode::Maker,
+            sweep: false,
         }
     }
 
+    pub fn with_update_mode(mut self, update_mode: UpdateMode) -> Self {
+        self.update_mode = update_mode;
+        self
+    }
+
+    pub fn with_trade_mode(mut self, trade_mode: TradeMode) -> Self {
+        self.trade_mode = trade_mode;
+        self
+    }
+
+    /// Makes a trade also remove every level on its side priced better than the trade.
+    pub fn with_sweep(mut self, sweep: bool) -> Self {
+        self.sweep = sweep;
+        self
+    }
+
     #[inline]
     pub fn process(&mut self, event: Event) {
         if event.timestamp < self.last_updated || event.seq < self.last_sequence {
@@ -66,6 +92,7 @@ impl Orderbook {
 
     #[inline]
     fn process_lvl2(&mut self, event: Event) {
+        let event = self.to_absolute(event);
         match event.is_buy {
             true => {
                 if event.size == 0.0 {
@@ -108,24 +135,66 @@ impl Orderbook {
     }
 
     #[inline]
-    fn process_trade(&mut self, event: Event) {
-        if event.is_buy {
-            if let Ok(index) = self.bids.find_index_bids(event.price) {
-                let level = self.bids.get_mut(index);
-                if event.size >= level.size {
-                    self.bids.remove(index);
-                } else {
-                    level.size -= event.size;
+    fn to_absolute(&self, event: Event) -> Event {
+        match self.update_mode {
+            UpdateMode::Absolute => event,
+            UpdateMode::Delta => {
+                let current = match event.is_buy {
+                    true => self
+                        .bids
+                        .find_index_bids(event.price)
+                        .map(|i| self.bids.get(i)),
+                    false => self
+                        .asks
+                        .find_index_asks(event.price)
+                        .map(|i| self.asks.get(i)),
+                };
+
+                Event {
+                    size: (current.map_or(0.0, |level| level.size) + event.size).max(0.0),
+                    ..event
                 }
-            };
-        } else if let Ok(index) = self.asks.find_index_asks(event.price) {
-            let level = self.asks.get_mut(index);
+            }
+        }
+    }
+
+    #[inline]
+    fn process_trade(&mut self, event: Event) {
+        let is_bid = match self.trade_mode {
+            TradeMode::Maker => event.is_buy,
+            TradeMode::Aggressor => !event.is_buy,
+            TradeMode::Ignore => return,
+        };
+
+        let buf = match is_bid {
+            true => &mut self.bids,
+            false => &mut self.asks,
+        };
+
+        let mut index = match is_bid {
+            true => buf.find_index_bids(event.price),
+            false => buf.find_index_asks(event.price),
+        };
+
+        if self.sweep {
+            let (Ok(better) | Err(better)) = index;
+            buf.remove_first(better);
+            index = index.map(|_| 0).map_err(|_| 0);
+        }
+
+        if let Ok(index) = index {
+            let level = buf.get_mut(index);
             if event.size >= level.size {
-                self.asks.remove(index);
+                buf.remove(index);
             } else {
                 level.size -= event.size;
             }
         };
+
+        match is_bid {
+            true => self.best_bid = self.bids.first(),
+            false => self.best_ask = self.asks.first(),
+        }
     }
 
     pub fn best_bid(&self) -> Option<Level> {
@@ -168,6 +237,48 @@ impl Orderbook {
         result
     }
 
+    /// Bids from the best price to the worst.
+    pub fn bids(&self) -> impl DoubleEndedIterator<Item = &Level> {
+        self.bids.iter()
+    }
+
+    /// Asks from the best price to the worst.
+    pub fn asks(&self) -> impl DoubleEndedIterator<Item = &Level> {
+        self.asks.iter()
+    }
+
+    /// Bids priced between `low` and `high`, inclusive, from the best price.
+    pub fn bids_range(&self, low: f64, high: f64) -> impl Iterator<Item = &Level> {
+        self.bids()
+            .skip_while(move |level| level.price > high)
+            .take_while(move |level| level.price >= low)
+    }
+
+    /// Asks priced between `low` and `high`, inclusive, from the best price.
+    pub fn asks_range(&self, low: f64, high: f64) -> impl Iterator<Item = &Level> {
+        self.asks()
+            .skip_while(move |level| level.price < low)
+            .take_while(move |level| level.price <= high)
+    }
+
+    pub fn bid_at(&self, price: f64) -> Option<&Level> {
+        let index = self.bids.find_index_bids(price).ok()?;
+        Some(self.bids.get(index))
+    }
+
+    pub fn ask_at(&self, price: f64) -> Option<&Level> {
+        let index = self.asks.find_index_asks(price).ok()?;
+        Some(self.asks.get(index))
+    }
+
+    pub fn bids_len(&self) -> usize {
+        self.bids.len()
+    }
+
+    pub fn asks_len(&self) -> usize {
+        self.asks.len()
+    }
+
     #[inline]
     pub fn midprice(&self) -> Option<f64> {
         if let (Some(best_bid), Some(best_ask)) = (self.best_bid, self.best_ask) {
@@ -193,6 +304,8 @@ impl Orderbook {
 pub struct Buffer {
     buf: [Level; 500],
     limit: f64,
+    /// Number of occupied levels, which are always at the front.
+    len: usize,
 }
 
 impl Buffer {
@@ -204,6 +317,7 @@ impl Buffer {
                 [Level::maximum(); 500]
             },
             limit: if is_bid { f64::MIN } else { f64::MAX },
+            len: 0,
         }
     }
 
@@ -289,10 +403,38 @@ impl Buffer {
         level.size = 0.0;
 
         self.move_back(index);
+        self.len -= 1;
 
         removed
     }
 
+    /// Removes the first `count` levels, shifting the rest to the front.
+    pub fn remove_first(&mut self, count: usize) {
+        let count = count.min(self.buf.len());
+        let len = self.buf.len();
+        let na = self.limit;
+
+        self.buf.rotate_left(count);
+        self.buf[len - count..].iter_mut().for_each(|level| {
+            level.price = na;
+            level.size = 0.0;
+        });
+        self.len = self.len.saturating_sub(count);
+    }
+
+    /// Occupied levels, in the order of the buffer.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Level> + ExactSizeIterator {
+        self.buf[..self.len].iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub fn first(&self) -> Option<Level> {
         self.buf
             .into_iter()
@@ -311,6 +453,9 @@ impl Buffer {
         if index >= self.buf.len() {
             return;
         }
+        // the last level falls off a full buffer
+        self.len = (self.len + 1).min(self.buf.len());
+
         let to_replace = self.get_mut(index);
         let mut replaced = replace(to_replace, level);
         if replaced.price == self.limit {
@@ -342,6 +487,8 @@ impl Buffer {
 mod tests {
     use super::*;
 
+    crate::backend_tests::backend_tests!(Orderbook::new());
+
     #[test]
     fn process_lvl2_bids() {
         let mut ob = Orderbook::new();
@@ -1255,4 +1402,57 @@ mod tests {
 
         assert_eq!(weighted_midprice, 16.8)
     }
+
+    #[test]
+    fn buffer_len() {
+        let mut ob = Orderbook::new();
+
+        let event = Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 1.0,
+            size: 1.0,
+        };
+
+        for i in 0..510 {
+            ob.process(Event {
+                price: 1.0 + i as f64,
+                ..event
+            });
+        }
+
+        // the worst bids fall off the full buffer
+        assert_eq!(ob.bids_len(), 500);
+        assert_eq!(ob.bids().count(), 500);
+        assert_eq!(ob.bids().next_back().map(|level| level.price), Some(11.0));
+
+        ob.process(Event {
+            price: 510.0,
+            size: 0.0,
+            ..event
+        });
+        ob.process(Event {
+            price: 505.0,
+            size: 0.0,
+            ..event
+        });
+
+        assert_eq!(ob.bids_len(), 498);
+        assert_eq!(ob.bids().next().map(|level| level.price), Some(509.0));
+
+        let mut ob = ob.with_sweep(true);
+        ob.process(Event {
+            is_trade: true,
+            is_buy: true,
+            price: 500.0,
+            size: 0.5,
+            ..event
+        });
+
+        assert_eq!(ob.bids_len(), 490);
+        assert_eq!(ob.bids().count(), 490);
+        assert_eq!(ob.best_bid(), Some(Level::new(500.0, 0.5)));
+    }
 }
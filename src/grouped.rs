@@ -0,0 +1,166 @@
+use crate::{level::Level, orderbook::Orderbook};
+use std::iter::Peekable;
+
+/// Tolerance for prices that sit on a group boundary but are not exact multiples in floating
+/// point, such as 0.3 with an increment of 0.1.
+const EPSILON: f64 = 1e-9;
+
+/// Read-only view of a book with its levels bucketed into a coarser price increment.
+///
+/// Bids are floored and asks are ceiled to the increment, so a grouped level never shows a
+/// better price than the levels it holds. Groups are merged lazily from the book's levels.
+#[derive(Debug, Clone, Copy)]
+pub struct Grouped<'a> {
+    book: &'a Orderbook,
+    increment: f64,
+}
+
+impl<'a> Grouped<'a> {
+    pub fn new(book: &'a Orderbook, increment: f64) -> Self {
+        assert!(increment > 0.0, "increment must be positive");
+        Self { book, increment }
+    }
+
+    pub fn increment(&self) -> f64 {
+        self.increment
+    }
+
+    /// Grouped bids from the best price to the worst.
+    pub fn bids(&self) -> impl Iterator<Item = Level> + 'a {
+        Groups {
+            levels: self.book.bids().peekable(),
+            increment: self.increment,
+            offset: EPSILON,
+            round: f64::floor,
+        }
+    }
+
+    /// Grouped asks from the best price to the worst.
+    pub fn asks(&self) -> impl Iterator<Item = Level> + 'a {
+        Groups {
+            levels: self.book.asks().peekable(),
+            increment: self.increment,
+            offset: -EPSILON,
+            round: f64::ceil,
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<Level> {
+        self.bids().next()
+    }
+
+    pub fn best_ask(&self) -> Option<Level> {
+        self.asks().next()
+    }
+
+    /// Top `n` grouped bids, reading only the levels they hold.
+    pub fn top_bids(&self, n: usize) -> Vec<Level> {
+        self.bids().take(n).collect()
+    }
+
+    /// Top `n` grouped asks, reading only the levels they hold.
+    pub fn top_asks(&self, n: usize) -> Vec<Level> {
+        self.asks().take(n).collect()
+    }
+}
+
+/// Merges consecutive levels of a side that fall in the same group.
+struct Groups<I: Iterator> {
+    levels: Peekable<I>,
+    increment: f64,
+    offset: f64,
+    round: fn(f64) -> f64,
+}
+
+impl<I: Iterator> Groups<I> {
+    fn group(&self, price: f64) -> f64 {
+        (self.round)(price / self.increment + self.offset)
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a Level>> Iterator for Groups<I> {
+    type Item = Level;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = self.levels.next()?;
+        let group = self.group(first.price);
+        let mut size = first.size;
+
+        while let Some(&&level) = self.levels.peek() {
+            if self.group(level.price) != group {
+                break;
+            }
+            size += level.size;
+            self.levels.next();
+        }
+
+        Some(Level {
+            price: group * self.increment,
+            size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+
+    #[test]
+    fn grouped_levels() {
+        let mut book = Orderbook::new(0.1);
+
+        let event = Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 99.9,
+            size: 1.0,
+        };
+
+        for (seq, (is_buy, price, size)) in [
+            (true, 99.9, 1.0),
+            (true, 99.5, 2.0),
+            (true, 99.0, 3.0),
+            (true, 98.7, 4.0),
+            (false, 100.1, 1.0),
+            (false, 101.0, 2.0),
+            (false, 101.2, 3.0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            book.process(Event {
+                seq: seq as u64,
+                is_buy,
+                price,
+                size,
+                ..event
+            });
+        }
+
+        let grouped = book.grouped(1.0);
+
+        assert_eq!(
+            grouped.top_bids(2),
+            [Level::new(99.0, 6.0), Level::new(98.0, 4.0)]
+        );
+        assert_eq!(
+            grouped.asks().collect::<Vec<_>>(),
+            [Level::new(101.0, 3.0), Level::new(102.0, 3.0)]
+        );
+        assert_eq!(grouped.best_bid().map(|bid| bid.price), Some(99.0));
+        assert_eq!(
+            book.grouped(0.5).best_ask().map(|ask| ask.price),
+            Some(100.5)
+        );
+        assert!(Orderbook::new(0.1).grouped(1.0).best_bid().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "increment must be positive")]
+    fn zero_increment() {
+        Orderbook::new(0.1).grouped(0.0);
+    }
+}
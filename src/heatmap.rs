@@ -0,0 +1,353 @@
+use crate::{event::Event, orderbook::Orderbook};
+
+/// One time bucket of a heatmap, with a row per price bucket from `low` upwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeatmapColumn {
+    pub start: u64,
+    /// Lower bound of the first price bucket.
+    pub low: f64,
+    /// Resting bid size averaged over the time bucket.
+    pub bids: Vec<f64>,
+    /// Resting ask size averaged over the time bucket.
+    pub asks: Vec<f64>,
+    /// Traded size.
+    pub trades: Vec<f64>,
+}
+
+impl HeatmapColumn {
+    fn new(start: u64, first: i64, price_bucket: f64, rows: usize) -> Self {
+        Self {
+            start,
+            low: first as f64 * price_bucket,
+            bids: vec![0.0; rows],
+            asks: vec![0.0; rows],
+            trades: vec![0.0; rows],
+        }
+    }
+}
+
+/// Default cap on the columns closed by a single event.
+const MAX_COLUMNS: usize = 1024;
+
+/// Builds a time by price matrix of resting and traded size from the events fed to a book.
+///
+/// Each column covers `rows` price buckets centred on the midprice at the start of its time
+/// bucket, so the window follows the mid. Sizes outside the window are left out. An event closes
+/// at most 1024 columns by default, see [`Heatmap::with_max_columns`].
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    book: Orderbook,
+    interval: u64,
+    price_bucket: f64,
+    rows: usize,
+    max_columns: usize,
+    current: Option<HeatmapColumn>,
+    /// Price bucket of the first row of the current column.
+    first: i64,
+    /// Time from which the current column holds resting size.
+    since: u64,
+    last: u64,
+}
+
+impl Heatmap {
+    pub fn new(book: Orderbook, interval: u64, price_bucket: f64, rows: usize) -> Self {
+        assert!(price_bucket > 0.0, "price bucket must be positive");
+        assert!(rows > 0, "a heatmap needs at least one row");
+
+        Self {
+            book,
+            interval: interval.max(1),
+            price_bucket,
+            rows,
+            max_columns: MAX_COLUMNS,
+            current: None,
+            first: 0,
+            since: 0,
+            last: 0,
+        }
+    }
+
+    /// Caps the columns closed by a single event. The rest of a longer gap is skipped and the
+    /// resting size over it is left out, so a jump in timestamps cannot emit unbounded columns.
+    pub fn with_max_columns(mut self, max_columns: usize) -> Self {
+        self.max_columns = max_columns.max(1);
+        self
+    }
+
+    pub fn book(&self) -> &Orderbook {
+        &self.book
+    }
+
+    pub fn into_inner(self) -> Orderbook {
+        self.book
+    }
+
+    fn bucket(&self, price: f64) -> i64 {
+        (price / self.price_bucket).floor() as i64
+    }
+
+    fn row(&self, price: f64) -> Option<usize> {
+        usize::try_from(self.bucket(price) - self.first)
+            .ok()
+            .filter(|&row| row < self.rows)
+    }
+
+    /// Starts a column around the midprice, or the best price or `fallback` on a one sided book.
+    fn open(&mut self, start: u64, fallback: f64) {
+        let center = self
+            .book
+            .midprice()
+            .or(self.book.best_bid().map(|bid| bid.price))
+            .or(self.book.best_ask().map(|ask| ask.price))
+            .unwrap_or(fallback);
+
+        self.first = self.bucket(center) - (self.rows / 2) as i64;
+        self.since = start;
+        self.current = Some(HeatmapColumn::new(
+            start,
+            self.first,
+            self.price_bucket,
+            self.rows,
+        ));
+    }
+
+    /// Adds the resting size of the book from the last update until `to`.
+    fn accumulate(&mut self, to: u64) {
+        let elapsed = to.saturating_sub(self.last) as f64;
+        self.last = self.last.max(to);
+
+        if elapsed == 0.0 {
+            return;
+        }
+
+        let Some(column) = self.current.as_mut() else {
+            return;
+        };
+
+        let (first, rows, price_bucket) = (self.first, self.rows, self.price_bucket);
+        let row = |price: f64| {
+            usize::try_from((price / price_bucket).floor() as i64 - first)
+                .ok()
+                .filter(|&row| row < rows)
+        };
+
+        let low = first as f64 * price_bucket;
+        let high = (first + rows as i64) as f64 * price_bucket;
+
+        for level in self.book.bids_range(low, high) {
+            if let Some(row) = row(level.price) {
+                column.bids[row] += level.size * elapsed;
+            }
+        }
+        for level in self.book.asks_range(low, high) {
+            if let Some(row) = row(level.price) {
+                column.asks[row] += level.size * elapsed;
+            }
+        }
+    }
+
+    /// Averages the resting sizes of the current column over the time it covered until `end`.
+    fn close(&mut self, end: u64) -> Option<HeatmapColumn> {
+        let mut column = self.current.take()?;
+
+        let duration = end.saturating_sub(self.since);
+        if duration > 0 {
+            let duration = duration as f64;
+            column
+                .bids
+                .iter_mut()
+                .chain(column.asks.iter_mut())
+                .for_each(|size| *size /= duration);
+        }
+
+        Some(column)
+    }
+
+    /// Processes an event, returning the columns of the time buckets it closed.
+    pub fn process(&mut self, event: Event) -> Vec<HeatmapColumn> {
+        let mut closed = Vec::new();
+
+        while let Some(end) = self
+            .current
+            .as_ref()
+            .map(|column| column.start + self.interval)
+            .filter(|&end| event.timestamp >= end)
+        {
+            if closed.len() == self.max_columns {
+                let start = event.timestamp - event.timestamp % self.interval;
+                self.last = start;
+                self.open(start, event.price);
+                break;
+            }
+
+            self.accumulate(end);
+            closed.extend(self.close(end));
+            self.open(end, event.price);
+        }
+
+        self.accumulate(event.timestamp);
+        self.book.process(event);
+
+        if self.current.is_none() {
+            self.last = event.timestamp;
+            self.open(
+                event.timestamp - event.timestamp % self.interval,
+                event.price,
+            );
+            self.since = event.timestamp;
+        }
+
+        if event.is_trade {
+            if let Some(row) = self.row(event.price) {
+                if let Some(column) = self.current.as_mut() {
+                    column.trades[row] += event.size;
+                }
+            }
+        }
+
+        closed
+    }
+
+    /// Closes the current column at the last event, averaging over the time it covered.
+    pub fn flush(&mut self) -> Option<HeatmapColumn> {
+        self.close(self.last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_weighted_sizes() {
+        let mut heatmap = Heatmap::new(Orderbook::new(0.01), 10, 1.0, 4);
+
+        let event = Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 99.0,
+            size: 2.0,
+        };
+
+        assert!(heatmap.process(event).is_empty());
+        assert!(heatmap
+            .process(Event {
+                seq: 1,
+                is_buy: false,
+                price: 101.0,
+                size: 1.0,
+                ..event
+            })
+            .is_empty());
+        assert!(heatmap
+            .process(Event {
+                timestamp: 5,
+                seq: 2,
+                size: 4.0,
+                ..event
+            })
+            .is_empty());
+        assert!(heatmap
+            .process(Event {
+                timestamp: 6,
+                seq: 3,
+                is_trade: true,
+                is_buy: false,
+                price: 101.0,
+                size: 0.5,
+            })
+            .is_empty());
+
+        let columns = heatmap.process(Event {
+            timestamp: 25,
+            seq: 4,
+            price: 98.0,
+            ..event
+        });
+
+        assert_eq!(columns.len(), 2);
+
+        // The window was centred on the bid before the ask arrived.
+        assert_eq!(columns[0].low, 97.0);
+        assert_eq!(columns[0].bids, [0.0, 0.0, 3.0, 0.0]);
+        assert_eq!(columns[0].asks, [0.0, 0.0, 0.0, 0.0]);
+
+        // The next column follows the mid at 100.
+        assert_eq!(columns[1].low, 98.0);
+        assert_eq!(columns[1].bids, [0.0, 4.0, 0.0, 0.0]);
+        assert_eq!(columns[1].asks, [0.0, 0.0, 0.0, 0.5]);
+        assert_eq!(columns[1].trades, [0.0; 4]);
+
+        let column = heatmap.flush().unwrap();
+
+        assert_eq!(column.start, 20);
+        assert_eq!(column.bids, [0.0, 4.0, 0.0, 0.0]);
+        assert!(heatmap.flush().is_none());
+    }
+
+    #[test]
+    fn skips_long_gaps() {
+        let mut heatmap = Heatmap::new(Orderbook::new(0.01), 10, 1.0, 4).with_max_columns(2);
+
+        let event = Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 99.0,
+            size: 2.0,
+        };
+
+        assert!(heatmap.process(event).is_empty());
+
+        let columns = heatmap.process(Event {
+            timestamp: 1_700_000_000_000_005,
+            seq: 1,
+            ..event
+        });
+
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns[1].start, 10);
+        assert_eq!(columns[1].bids, [0.0, 0.0, 2.0, 0.0]);
+
+        let column = heatmap.flush().unwrap();
+
+        assert_eq!(column.start, 1_700_000_000_000_000);
+        assert_eq!(column.bids, [0.0, 0.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn partial_first_column() {
+        let mut heatmap = Heatmap::new(Orderbook::new(0.01), 10, 1.0, 4);
+
+        let event = Event {
+            timestamp: 5,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 99.0,
+            size: 2.0,
+        };
+
+        heatmap.process(event);
+
+        let columns = heatmap.process(Event {
+            timestamp: 1_700_000_000_000_000_000,
+            seq: 1,
+            ..event
+        });
+
+        // the first column only covers the time after its first event
+        assert_eq!(columns.len(), MAX_COLUMNS);
+        assert_eq!(columns[0].bids, [0.0, 0.0, 2.0, 0.0]);
+        assert_eq!(columns[1].bids, [0.0, 0.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "price bucket must be positive")]
+    fn zero_price_bucket() {
+        Heatmap::new(Orderbook::new(0.01), 10, 0.0, 4);
+    }
+}
@@ -0,0 +1,574 @@
+use crate::{
+    event::Event,
+    l3_orderbook::{Order, Orderbook},
+};
+use std::error;
+use std::fmt;
+use std::io::{self, Read};
+
+/// Prices in ITCH are integers with 4 implied decimals.
+const PRICE_SCALE: f64 = 10_000.0;
+
+/// Nasdaq TotalView-ITCH 5.0 messages relevant to the order book. Every other message type is
+/// kept as [`Message::Other`]. Timestamps are nanoseconds since midnight.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// `R`
+    StockDirectory {
+        stock_locate: u16,
+        timestamp: u64,
+        stock: String,
+    },
+    /// `A` and `F`
+    AddOrder {
+        stock_locate: u16,
+        timestamp: u64,
+        order_ref: u64,
+        is_buy: bool,
+        shares: u32,
+        stock: String,
+        price: f64,
+    },
+    /// `E`
+    OrderExecuted {
+        stock_locate: u16,
+        timestamp: u64,
+        order_ref: u64,
+        shares: u32,
+        match_number: u64,
+    },
+    /// `C`
+    OrderExecutedWithPrice {
+        stock_locate: u16,
+        timestamp: u64,
+        order_ref: u64,
+        shares: u32,
+        match_number: u64,
+        price: f64,
+    },
+    /// `X`
+    OrderCancel {
+        stock_locate: u16,
+        timestamp: u64,
+        order_ref: u64,
+        shares: u32,
+    },
+    /// `D`
+    OrderDelete {
+        stock_locate: u16,
+        timestamp: u64,
+        order_ref: u64,
+    },
+    /// `U`
+    OrderReplace {
+        stock_locate: u16,
+        timestamp: u64,
+        original_ref: u64,
+        new_ref: u64,
+        shares: u32,
+        price: f64,
+    },
+    Other {
+        kind: u8,
+        stock_locate: u16,
+        timestamp: u64,
+    },
+}
+
+impl Message {
+    /// Parses a single message, without its length prefix.
+    pub fn parse(buf: &[u8]) -> Result<Self, ItchError> {
+        let kind = *buf
+            .first()
+            .ok_or(ItchError::Truncated { kind: 0, len: 0 })?;
+
+        let expected = match kind {
+            b'R' => 39,
+            b'A' => 36,
+            b'F' => 40,
+            b'E' => 31,
+            b'C' => 36,
+            b'X' => 23,
+            b'D' => 19,
+            b'U' => 35,
+            _ => 11,
+        };
+
+        if buf.len() < expected {
+            return Err(ItchError::Truncated {
+                kind,
+                len: buf.len(),
+            });
+        }
+
+        let stock_locate = be_u16(&buf[1..3]);
+        let timestamp = be_u48(&buf[5..11]);
+
+        let message = match kind {
+            b'R' => Message::StockDirectory {
+                stock_locate,
+                timestamp,
+                stock: stock(&buf[11..19]),
+            },
+            b'A' | b'F' => Message::AddOrder {
+                stock_locate,
+                timestamp,
+                order_ref: be_u64(&buf[11..19]),
+                is_buy: match buf[19] {
+                    b'B' => true,
+                    b'S' => false,
+                    side => return Err(ItchError::InvalidSide(side)),
+                },
+                shares: be_u32(&buf[20..24]),
+                stock: stock(&buf[24..32]),
+                price: price(&buf[32..36]),
+            },
+            b'E' => Message::OrderExecuted {
+                stock_locate,
+                timestamp,
+                order_ref: be_u64(&buf[11..19]),
+                shares: be_u32(&buf[19..23]),
+                match_number: be_u64(&buf[23..31]),
+            },
+            b'C' => Message::OrderExecutedWithPrice {
+                stock_locate,
+                timestamp,
+                order_ref: be_u64(&buf[11..19]),
+                shares: be_u32(&buf[19..23]),
+                match_number: be_u64(&buf[23..31]),
+                price: price(&buf[32..36]),
+            },
+            b'X' => Message::OrderCancel {
+                stock_locate,
+                timestamp,
+                order_ref: be_u64(&buf[11..19]),
+                shares: be_u32(&buf[19..23]),
+            },
+            b'D' => Message::OrderDelete {
+                stock_locate,
+                timestamp,
+                order_ref: be_u64(&buf[11..19]),
+            },
+            b'U' => Message::OrderReplace {
+                stock_locate,
+                timestamp,
+                original_ref: be_u64(&buf[11..19]),
+                new_ref: be_u64(&buf[19..27]),
+                shares: be_u32(&buf[27..31]),
+                price: price(&buf[31..35]),
+            },
+            kind => Message::Other {
+                kind,
+                stock_locate,
+                timestamp,
+            },
+        };
+
+        Ok(message)
+    }
+
+    pub fn stock_locate(&self) -> u16 {
+        match *self {
+            Message::StockDirectory { stock_locate, .. }
+            | Message::AddOrder { stock_locate, .. }
+            | Message::OrderExecuted { stock_locate, .. }
+            | Message::OrderExecutedWithPrice { stock_locate, .. }
+            | Message::OrderCancel { stock_locate, .. }
+            | Message::OrderDelete { stock_locate, .. }
+            | Message::OrderReplace { stock_locate, .. }
+            | Message::Other { stock_locate, .. } => stock_locate,
+        }
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        match *self {
+            Message::StockDirectory { timestamp, .. }
+            | Message::AddOrder { timestamp, .. }
+            | Message::OrderExecuted { timestamp, .. }
+            | Message::OrderExecutedWithPrice { timestamp, .. }
+            | Message::OrderCancel { timestamp, .. }
+            | Message::OrderDelete { timestamp, .. }
+            | Message::OrderReplace { timestamp, .. }
+            | Message::Other { timestamp, .. } => timestamp,
+        }
+    }
+}
+
+#[inline]
+fn be_u16(buf: &[u8]) -> u16 {
+    u16::from_be_bytes([buf[0], buf[1]])
+}
+
+#[inline]
+fn be_u32(buf: &[u8]) -> u32 {
+    u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]])
+}
+
+#[inline]
+fn be_u48(buf: &[u8]) -> u64 {
+    buf[..6]
+        .iter()
+        .fold(0, |acc, &byte| (acc << 8) | byte as u64)
+}
+
+#[inline]
+fn be_u64(buf: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&buf[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+#[inline]
+fn price(buf: &[u8]) -> f64 {
+    be_u32(buf) as f64 / PRICE_SCALE
+}
+
+fn stock(buf: &[u8]) -> String {
+    String::from_utf8_lossy(buf).trim_end().to_string()
+}
+
+/// Streaming reader of ITCH files where every message is prefixed by its length as a big-endian
+/// `u16`, as in the Nasdaq `BinaryFILE` downloads.
+#[derive(Debug)]
+pub struct ItchReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+}
+
+impl<R: Read> ItchReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::with_capacity(64),
+        }
+    }
+
+    fn read_message(&mut self) -> Result<Option<Message>, ItchError> {
+        let mut len = [0; 2];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+
+        self.buf.resize(u16::from_be_bytes(len) as usize, 0);
+        self.reader.read_exact(&mut self.buf)?;
+
+        Message::parse(&self.buf).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for ItchReader<R> {
+    type Item = Result<Message, ItchError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_message().transpose()
+    }
+}
+
+#[derive(Debug)]
+pub enum ItchError {
+    Io(io::Error),
+    /// The message is shorter than its type requires.
+    Truncated {
+        kind: u8,
+        len: usize,
+    },
+    /// Buy/sell indicator other than `B` or `S`.
+    InvalidSide(u8),
+}
+
+impl fmt::Display for ItchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItchError::Io(err) => write!(f, "{}", err),
+            ItchError::Truncated { kind, len } => {
+                write!(f, "message {:?} truncated to {} bytes", *kind as char, len)
+            }
+            ItchError::InvalidSide(side) => write!(f, "invalid side {:?}", *side as char),
+        }
+    }
+}
+
+impl error::Error for ItchError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ItchError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ItchError {
+    fn from(value: io::Error) -> Self {
+        ItchError::Io(value)
+    }
+}
+
+/// Drives an order-level book for a single stock from ITCH messages.
+///
+/// The stock is selected by symbol, its locate code being learned from the stock directory
+/// message, or directly by locate code. Every processed message returns the level 2 [`Event`]s of
+/// the price levels it changed, timestamped in microseconds since midnight and sequenced by
+/// message count. Executions only show up as level changes, so the events can be fed to
+/// [`crate::orderbook::Orderbook`] without depleting levels twice.
+#[derive(Debug, Clone)]
+pub struct ItchBook {
+    book: Orderbook,
+    symbol: Option<String>,
+    stock_locate: Option<u16>,
+    seq: u64,
+}
+
+impl ItchBook {
+    pub fn new(symbol: &str, tick_size: f64) -> Self {
+        Self {
+            book: Orderbook::new(tick_size),
+            symbol: Some(symbol.to_string()),
+            stock_locate: None,
+            seq: 0,
+        }
+    }
+
+    pub fn with_stock_locate(stock_locate: u16, tick_size: f64) -> Self {
+        Self {
+            book: Orderbook::new(tick_size),
+            symbol: None,
+            stock_locate: Some(stock_locate),
+            seq: 0,
+        }
+    }
+
+    pub fn book(&self) -> &Orderbook {
+        &self.book
+    }
+
+    pub fn stock_locate(&self) -> Option<u16> {
+        self.stock_locate
+    }
+
+    pub fn process(&mut self, message: &Message) -> impl Iterator<Item = Event> {
+        let mut events = [None, None];
+
+        if let Message::StockDirectory {
+            stock_locate,
+            stock,
+            ..
+        } = message
+        {
+            if self.symbol.as_deref() == Some(stock.as_str()) {
+                self.stock_locate = Some(*stock_locate);
+            }
+        }
+
+        if self.stock_locate != Some(message.stock_locate()) {
+            return events.into_iter().flatten();
+        }
+
+        let timestamp = message.timestamp() / 1000;
+        self.seq += 1;
+        let seq = self.seq;
+
+        match *message {
+            Message::AddOrder {
+                order_ref,
+                is_buy,
+                shares,
+                price,
+                ..
+            } => {
+                let order = Order::new(order_ref, is_buy, price, shares as f64);
+                events[0] = self.book.add(timestamp, seq, order);
+            }
+            Message::OrderExecuted {
+                order_ref, shares, ..
+            }
+            | Message::OrderExecutedWithPrice {
+                order_ref, shares, ..
+            }
+            | Message::OrderCancel {
+                order_ref, shares, ..
+            } => {
+                events[0] = self.book.reduce(timestamp, seq, order_ref, shares as f64);
+            }
+            Message::OrderDelete { order_ref, .. } => {
+                events[0] = self.book.delete(timestamp, seq, order_ref);
+            }
+            Message::OrderReplace {
+                original_ref,
+                new_ref,
+                shares,
+                price,
+                ..
+            } => {
+                let (removed, added) =
+                    self.book
+                        .replace(timestamp, seq, original_ref, new_ref, price, shares as f64);
+                events = [removed, added];
+            }
+            Message::StockDirectory { .. } | Message::Other { .. } => {}
+        }
+
+        events.into_iter().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    fn header(kind: u8, stock_locate: u16, timestamp: u64) -> Vec<u8> {
+        let mut buf = vec![kind];
+        buf.extend_from_slice(&stock_locate.to_be_bytes());
+        buf.extend_from_slice(&0u16.to_be_bytes());
+        buf.extend_from_slice(&timestamp.to_be_bytes()[2..]);
+        buf
+    }
+
+    fn stock_directory(stock_locate: u16, stock: &str) -> Vec<u8> {
+        let mut buf = header(b'R', stock_locate, 0);
+        buf.extend_from_slice(format!("{:<8}", stock).as_bytes());
+        buf.resize(39, b' ');
+        buf
+    }
+
+    fn add_order(stock_locate: u16, order_ref: u64, side: u8, shares: u32, price: u32) -> Vec<u8> {
+        let mut buf = header(b'A', stock_locate, 34_200_000_000_000);
+        buf.extend_from_slice(&order_ref.to_be_bytes());
+        buf.push(side);
+        buf.extend_from_slice(&shares.to_be_bytes());
+        buf.extend_from_slice(b"AAPL    ");
+        buf.extend_from_slice(&price.to_be_bytes());
+        buf
+    }
+
+    fn executed(stock_locate: u16, order_ref: u64, shares: u32) -> Vec<u8> {
+        let mut buf = header(b'E', stock_locate, 34_200_000_001_000);
+        buf.extend_from_slice(&order_ref.to_be_bytes());
+        buf.extend_from_slice(&shares.to_be_bytes());
+        buf.extend_from_slice(&7u64.to_be_bytes());
+        buf
+    }
+
+    fn replace(stock_locate: u16, original: u64, new: u64, shares: u32, price: u32) -> Vec<u8> {
+        let mut buf = header(b'U', stock_locate, 34_200_000_002_000);
+        buf.extend_from_slice(&original.to_be_bytes());
+        buf.extend_from_slice(&new.to_be_bytes());
+        buf.extend_from_slice(&shares.to_be_bytes());
+        buf.extend_from_slice(&price.to_be_bytes());
+        buf
+    }
+
+    fn delete(stock_locate: u16, order_ref: u64) -> Vec<u8> {
+        let mut buf = header(b'D', stock_locate, 34_200_000_003_000);
+        buf.extend_from_slice(&order_ref.to_be_bytes());
+        buf
+    }
+
+    fn file(messages: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for message in messages {
+            data.extend_from_slice(&(message.len() as u16).to_be_bytes());
+            data.extend_from_slice(message);
+        }
+        data
+    }
+
+    #[test]
+    fn parse_add_order() {
+        let message = Message::parse(&add_order(5, 42, b'B', 100, 1_502_500)).unwrap();
+
+        assert_eq!(
+            message,
+            Message::AddOrder {
+                stock_locate: 5,
+                timestamp: 34_200_000_000_000,
+                order_ref: 42,
+                is_buy: true,
+                shares: 100,
+                stock: "AAPL".to_string(),
+                price: 150.25,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let mut buf = add_order(5, 42, b'Z', 100, 1_502_500);
+
+        assert!(matches!(
+            Message::parse(&buf),
+            Err(ItchError::InvalidSide(b'Z'))
+        ));
+
+        buf.truncate(20);
+
+        assert!(matches!(
+            Message::parse(&buf),
+            Err(ItchError::Truncated {
+                kind: b'A',
+                len: 20
+            })
+        ));
+    }
+
+    #[test]
+    fn read_file() {
+        let data = file(&[
+            stock_directory(5, "AAPL"),
+            add_order(5, 1, b'B', 100, 1_500_000),
+            header(b'S', 0, 10),
+        ]);
+
+        let messages = ItchReader::new(data.as_slice())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(
+            messages[2],
+            Message::Other {
+                kind: b'S',
+                stock_locate: 0,
+                timestamp: 10
+            }
+        );
+    }
+
+    #[test]
+    fn drive_book() {
+        let data = file(&[
+            add_order(5, 1, b'B', 100, 1_500_000),
+            stock_directory(5, "AAPL"),
+            add_order(6, 2, b'B', 100, 1_500_000),
+            add_order(5, 3, b'B', 100, 1_500_000),
+            add_order(5, 4, b'B', 200, 1_500_000),
+            add_order(5, 5, b'S', 50, 1_501_000),
+            executed(5, 3, 40),
+            replace(5, 4, 6, 300, 1_499_000),
+            delete(5, 5),
+        ]);
+
+        let mut itch = ItchBook::new("AAPL", 0.0001);
+        let mut events = Vec::new();
+
+        for message in ItchReader::new(data.as_slice()) {
+            events.extend(itch.process(&message.unwrap()));
+        }
+
+        assert_eq!(itch.stock_locate(), Some(5));
+        assert_eq!(events.len(), 7);
+
+        assert_eq!(events[0].timestamp, 34_200_000_000);
+        assert_eq!(events[1].size, 300.0);
+        assert_eq!(events[3].size, 260.0);
+        assert_eq!(events[4].size, 60.0);
+        assert_eq!(events[5].price, 149.9);
+        assert_eq!(events[5].size, 300.0);
+        assert_eq!(events[6].size, 0.0);
+
+        assert_eq!(itch.book().best_bid(), Some(Level::new(150.0, 60.0)));
+        assert_eq!(itch.book().best_ask(), None);
+    }
+}
@@ -0,0 +1,425 @@
+use crate::{event::Event, level::Level};
+use std::collections::{BTreeMap, HashMap};
+
+/// Resting order of an order-level book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Order {
+    pub id: u64,
+    pub is_buy: bool,
+    pub price: f64,
+    pub size: f64,
+}
+
+impl Order {
+    pub fn new(id: u64, is_buy: bool, price: f64, size: f64) -> Self {
+        Self {
+            id,
+            is_buy,
+            price,
+            size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PriceLevel {
+    level: Level,
+    orders: usize,
+}
+
+/// Order-level (level 3) orderbook keeping every resting order and the aggregated size per price.
+///
+/// Every change returns the level 2 [`Event`] describing the new absolute size of the affected
+/// price level (size 0 when the level is gone), so the stream can be fed to
+/// [`crate::orderbook::Orderbook`] unchanged.
+#[derive(Debug, Default, Clone)]
+pub struct Orderbook {
+    orders: HashMap<u64, Order>,
+    bids: BTreeMap<u64, PriceLevel>,
+    asks: BTreeMap<u64, PriceLevel>,
+    last_updated: u64,
+    last_sequence: u64,
+    inv_tick_size: f64,
+}
+
+impl Orderbook {
+    pub fn new(tick_size: f64) -> Self {
+        Self {
+            orders: HashMap::new(),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            last_updated: 0,
+            last_sequence: 0,
+            inv_tick_size: 1.0 / tick_size,
+        }
+    }
+
+    /// Adds a new order. Orders with an id already in the book are ignored, use
+    /// [`Orderbook::modify`] to change them.
+    pub fn add(&mut self, timestamp: u64, seq: u64, order: Order) -> Option<Event> {
+        self.touch(timestamp, seq);
+
+        if order.size <= 0.0 || self.orders.contains_key(&order.id) {
+            return None;
+        }
+
+        self.orders.insert(order.id, order);
+
+        let price_ticks = self.price_ticks(order.price);
+        let levels = match order.is_buy {
+            true => &mut self.bids,
+            false => &mut self.asks,
+        };
+
+        let level = levels.entry(price_ticks).or_insert(PriceLevel {
+            level: Level::new(order.price, 0.0),
+            orders: 0,
+        });
+        level.level.size += order.size;
+        level.orders += 1;
+
+        Some(self.level_event(order.is_buy, price_ticks, order.price))
+    }
+
+    /// Reduces the size of an order by `size`, removing it when nothing is left.
+    /// Used for both executions and partial cancels.
+    pub fn reduce(&mut self, timestamp: u64, seq: u64, id: u64, size: f64) -> Option<Event> {
+        self.touch(timestamp, seq);
+
+        let order = self.orders.get_mut(&id)?;
+        let reduced = order.size.min(size);
+        order.size -= reduced;
+
+        let order = *order;
+        if order.size <= 0.0 {
+            self.orders.remove(&id);
+        }
+
+        let price_ticks = self.price_ticks(order.price);
+        let levels = match order.is_buy {
+            true => &mut self.bids,
+            false => &mut self.asks,
+        };
+
+        if let Some(level) = levels.get_mut(&price_ticks) {
+            level.level.size -= reduced;
+            if order.size <= 0.0 {
+                level.orders -= 1;
+            }
+            if level.orders == 0 || level.level.size <= 0.0 {
+                levels.remove(&price_ticks);
+            }
+        }
+
+        Some(self.level_event(order.is_buy, price_ticks, order.price))
+    }
+
+    /// Removes an order from the book.
+    pub fn delete(&mut self, timestamp: u64, seq: u64, id: u64) -> Option<Event> {
+        self.touch(timestamp, seq);
+
+        let order = self.orders.remove(&id)?;
+        let price_ticks = self.remove_from_level(&order);
+
+        Some(self.level_event(order.is_buy, price_ticks, order.price))
+    }
+
+    /// Removes `old_id` and adds a new order on the same side. Returns the event of the level the
+    /// order left and the event of the level it joined. The book is left unchanged when `new_id`
+    /// is already another order in the book.
+    pub fn replace(
+        &mut self,
+        timestamp: u64,
+        seq: u64,
+        old_id: u64,
+        new_id: u64,
+        price: f64,
+        size: f64,
+    ) -> (Option<Event>, Option<Event>) {
+        let Some(old) = self.orders.get(&old_id).copied() else {
+            self.touch(timestamp, seq);
+            return (None, None);
+        };
+        if new_id != old_id && self.orders.contains_key(&new_id) {
+            self.touch(timestamp, seq);
+            return (None, None);
+        }
+
+        let removed = self.delete(timestamp, seq, old_id);
+        let added = self.add(timestamp, seq, Order::new(new_id, old.is_buy, price, size));
+
+        // the same level left and joined only needs to be reported once
+        match (removed, added) {
+            (Some(removed), Some(added)) if removed.price == added.price => (None, Some(added)),
+            other => other,
+        }
+    }
+
+    /// Changes the price and size of an order, keeping its id and side.
+    pub fn modify(
+        &mut self,
+        timestamp: u64,
+        seq: u64,
+        id: u64,
+        price: f64,
+        size: f64,
+    ) -> (Option<Event>, Option<Event>) {
+        self.replace(timestamp, seq, id, id, price, size)
+    }
+
+    /// Removes every order from the book, keeping its configuration.
+    pub fn clear(&mut self) {
+        self.orders.clear();
+        self.bids.clear();
+        self.asks.clear();
+        self.last_updated = 0;
+        self.last_sequence = 0;
+    }
+
+    pub fn order(&self, id: u64) -> Option<&Order> {
+        self.orders.get(&id)
+    }
+
+    pub fn orders(&self) -> impl Iterator<Item = &Order> {
+        self.orders.values()
+    }
+
+    pub fn best_bid(&self) -> Option<Level> {
+        self.bids.values().next_back().map(|level| level.level)
+    }
+
+    pub fn best_ask(&self) -> Option<Level> {
+        self.asks.values().next().map(|level| level.level)
+    }
+
+    #[inline]
+    pub fn top_bids(&self, n: usize) -> Vec<Level> {
+        self.bids.values().rev().take(n).map(|l| l.level).collect()
+    }
+
+    #[inline]
+    pub fn top_asks(&self, n: usize) -> Vec<Level> {
+        self.asks.values().take(n).map(|l| l.level).collect()
+    }
+
+    /// Level 2 events rebuilding the current aggregated book, best levels first.
+    pub fn snapshot_events(&self) -> impl Iterator<Item = Event> + '_ {
+        let to_event = |is_buy: bool| {
+            move |level: &PriceLevel| Event {
+                timestamp: self.last_updated,
+                seq: self.last_sequence,
+                is_trade: false,
+                is_buy,
+                price: level.level.price,
+                size: level.level.size,
+            }
+        };
+
+        self.bids
+            .values()
+            .rev()
+            .map(to_event(true))
+            .chain(self.asks.values().map(to_event(false)))
+    }
+
+    #[inline]
+    pub fn midprice(&self) -> Option<f64> {
+        if let (Some(best_bid), Some(best_ask)) = (self.best_bid(), self.best_ask()) {
+            return Some((best_bid.price + best_ask.price) / 2.0);
+        }
+
+        None
+    }
+
+    #[inline]
+    fn price_ticks(&self, price: f64) -> u64 {
+        (price * self.inv_tick_size) as u64
+    }
+
+    #[inline]
+    fn touch(&mut self, timestamp: u64, seq: u64) {
+        self.last_updated = timestamp;
+        self.last_sequence = seq;
+    }
+
+    fn remove_from_level(&mut self, order: &Order) -> u64 {
+        let price_ticks = self.price_ticks(order.price);
+        let levels = match order.is_buy {
+            true => &mut self.bids,
+            false => &mut self.asks,
+        };
+
+        if let Some(level) = levels.get_mut(&price_ticks) {
+            level.level.size -= order.size;
+            level.orders -= 1;
+            if level.orders == 0 || level.level.size <= 0.0 {
+                levels.remove(&price_ticks);
+            }
+        }
+
+        price_ticks
+    }
+
+    fn level_event(&self, is_buy: bool, price_ticks: u64, price: f64) -> Event {
+        let levels = match is_buy {
+            true => &self.bids,
+            false => &self.asks,
+        };
+
+        Event {
+            timestamp: self.last_updated,
+            seq: self.last_sequence,
+            is_trade: false,
+            is_buy,
+            price,
+            size: levels.get(&price_ticks).map_or(0.0, |l| l.level.size),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_orders() {
+        let mut ob = Orderbook::new(0.01);
+
+        let event = ob.add(1, 1, Order::new(1, true, 10.0, 1.0)).unwrap();
+
+        assert!(event.is_buy);
+        assert!(!event.is_trade);
+        assert_eq!(event.price, 10.0);
+        assert_eq!(event.size, 1.0);
+
+        let event = ob.add(2, 2, Order::new(2, true, 10.0, 2.5)).unwrap();
+
+        assert_eq!(event.size, 3.5);
+        assert_eq!(event.timestamp, 2);
+        assert_eq!(event.seq, 2);
+
+        ob.add(3, 3, Order::new(3, false, 11.0, 1.0));
+        ob.add(4, 4, Order::new(4, true, 9.0, 1.0));
+
+        assert_eq!(ob.best_bid(), Some(Level::new(10.0, 3.5)));
+        assert_eq!(ob.best_ask(), Some(Level::new(11.0, 1.0)));
+        assert_eq!(
+            ob.top_bids(5),
+            [Level::new(10.0, 3.5), Level::new(9.0, 1.0)]
+        );
+        assert_eq!(ob.midprice(), Some(10.5));
+    }
+
+    #[test]
+    fn reduce_and_delete() {
+        let mut ob = Orderbook::new(0.01);
+
+        ob.add(0, 0, Order::new(1, false, 11.0, 1.0));
+        ob.add(0, 0, Order::new(2, false, 11.0, 2.0));
+
+        let event = ob.reduce(1, 1, 1, 0.4).unwrap();
+
+        assert_eq!(event.size, 2.6);
+        assert_eq!(ob.order(1).unwrap().size, 0.6);
+
+        let event = ob.reduce(2, 2, 1, 5.0).unwrap();
+
+        assert_eq!(event.size, 2.0);
+        assert_eq!(ob.order(1), None);
+
+        let event = ob.delete(3, 3, 2).unwrap();
+
+        assert_eq!(event.price, 11.0);
+        assert_eq!(event.size, 0.0);
+        assert_eq!(ob.best_ask(), None);
+
+        assert_eq!(ob.delete(4, 4, 2), None);
+        assert_eq!(ob.reduce(4, 4, 7, 1.0), None);
+    }
+
+    #[test]
+    fn replace_and_modify() {
+        let mut ob = Orderbook::new(0.01);
+
+        ob.add(0, 0, Order::new(1, true, 10.0, 1.0));
+        ob.add(0, 0, Order::new(2, true, 10.0, 1.0));
+
+        let (removed, added) = ob.replace(1, 1, 1, 3, 10.5, 2.0);
+
+        assert_eq!(removed.unwrap().size, 1.0);
+        assert_eq!(removed.unwrap().price, 10.0);
+        assert_eq!(added.unwrap().size, 2.0);
+        assert_eq!(added.unwrap().price, 10.5);
+        assert!(ob.order(3).unwrap().is_buy);
+
+        let (removed, added) = ob.modify(2, 2, 3, 10.5, 4.0);
+
+        assert_eq!(removed, None);
+        assert_eq!(added.unwrap().size, 4.0);
+
+        assert_eq!(ob.replace(3, 3, 42, 43, 1.0, 1.0), (None, None));
+
+        // replacing with the id of another resting order keeps both
+        assert_eq!(ob.replace(4, 4, 3, 2, 11.0, 1.0), (None, None));
+        assert_eq!(ob.order(3).unwrap().size, 4.0);
+        assert_eq!(
+            ob.top_bids(5),
+            [Level::new(10.5, 4.0), Level::new(10.0, 1.0)]
+        );
+    }
+
+    #[test]
+    fn events_rebuild_level2_book() {
+        use crate::orderbook::Orderbook as L2Orderbook;
+
+        let mut ob = Orderbook::new(0.01);
+        let mut l2 = L2Orderbook::new(0.01);
+
+        let mut events = vec![
+            ob.add(1, 1, Order::new(1, true, 10.0, 1.0)),
+            ob.add(2, 2, Order::new(2, false, 11.0, 1.0)),
+            ob.add(3, 3, Order::new(3, true, 10.0, 2.0)),
+            ob.reduce(4, 4, 1, 1.0),
+            ob.add(5, 5, Order::new(4, false, 10.5, 3.0)),
+        ];
+        let (removed, added) = ob.replace(6, 6, 2, 5, 10.75, 1.0);
+        events.push(removed);
+        events.push(added);
+
+        events.into_iter().flatten().for_each(|e| l2.process(e));
+
+        assert_eq!(l2.top_bids(5), ob.top_bids(5));
+        assert_eq!(l2.top_asks(5), ob.top_asks(5));
+
+        let snapshot = ob.snapshot_events().collect::<Vec<_>>();
+
+        assert_eq!(snapshot.len(), 3);
+        assert_eq!(snapshot[0].price, 10.0);
+        assert_eq!(snapshot[1].price, 10.5);
+    }
+
+    #[test]
+    fn duplicate_add_keeps_level2_book_in_sync() {
+        use crate::orderbook::Orderbook as L2Orderbook;
+
+        let mut ob = Orderbook::new(0.01);
+        let mut l2 = L2Orderbook::new(0.01);
+
+        let events = [
+            ob.add(1, 1, Order::new(1, true, 10.0, 1.0)),
+            ob.add(2, 2, Order::new(1, true, 9.0, 2.0)),
+            ob.add(3, 3, Order::new(1, false, 11.0, 0.0)),
+            ob.add(4, 4, Order::new(2, false, 11.0, 1.0)),
+        ];
+
+        assert_eq!(events[1], None);
+        assert_eq!(events[2], None);
+        assert_eq!(ob.order(1).unwrap().price, 10.0);
+
+        events.into_iter().flatten().for_each(|e| l2.process(e));
+
+        assert_eq!(l2.top_bids(5), ob.top_bids(5));
+        assert_eq!(l2.top_asks(5), ob.top_asks(5));
+        assert_eq!(ob.top_bids(5), [Level::new(10.0, 1.0)]);
+    }
+}
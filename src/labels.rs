@@ -0,0 +1,239 @@
+use crate::{event::Event, orderbook::Orderbook};
+use std::collections::VecDeque;
+
+/// How far ahead a forward return looks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Horizon {
+    /// The midprice once every event up to this many microseconds later is processed.
+    Time(u64),
+    /// The midprice after this many more events.
+    Events(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Down,
+    Flat,
+    Up,
+}
+
+/// A state marked with [`Labeler::mark`] and its forward returns, one per horizon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Labeled<T> {
+    pub item: T,
+    pub timestamp: u64,
+    pub midprice: Option<f64>,
+    /// Relative change of the midprice, `None` when either midprice is missing or the replay ended
+    /// before the horizon.
+    pub returns: Vec<Option<f64>>,
+    pub classes: Vec<Option<Class>>,
+}
+
+#[derive(Debug, Clone)]
+struct Pending<T> {
+    item: T,
+    timestamp: u64,
+    event: u64,
+    midprice: Option<f64>,
+    returns: Vec<Option<f64>>,
+}
+
+/// Labels book states with forward midprice returns in a single pass over a replay.
+///
+/// Marked states are buffered until every horizon has passed, so the buffer only holds the states
+/// within the longest horizon.
+#[derive(Debug, Clone)]
+pub struct Labeler<T> {
+    book: Orderbook,
+    horizons: Vec<Horizon>,
+    threshold: f64,
+    pending: VecDeque<Pending<T>>,
+    /// Position of the front of `pending` among all marked states.
+    head: usize,
+    /// Position of the first state not yet resolved, per horizon.
+    cursors: Vec<usize>,
+    events: u64,
+    timestamp: u64,
+}
+
+impl<T> Labeler<T> {
+    pub fn new(book: Orderbook, horizons: &[Horizon]) -> Self {
+        Self {
+            book,
+            horizons: horizons.to_vec(),
+            threshold: 0.0,
+            pending: VecDeque::new(),
+            head: 0,
+            cursors: vec![0; horizons.len()],
+            events: 0,
+            timestamp: 0,
+        }
+    }
+
+    /// Returns within `threshold` of 0 are classed as flat.
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn book(&self) -> &Orderbook {
+        &self.book
+    }
+
+    /// Marks the current state of the book to be labeled.
+    pub fn mark(&mut self, item: T) {
+        self.pending.push_back(Pending {
+            item,
+            timestamp: self.timestamp,
+            event: self.events,
+            midprice: self.book.midprice(),
+            returns: vec![None; self.horizons.len()],
+        });
+    }
+
+    /// Processes an event, returning the marked states whose horizons have all passed.
+    pub fn process(&mut self, event: Event) -> Vec<Labeled<T>> {
+        self.resolve(|horizon, pending| match horizon {
+            Horizon::Time(span) => event.timestamp > pending.timestamp + span,
+            Horizon::Events(_) => false,
+        });
+
+        self.book.process(event);
+        self.events += 1;
+        self.timestamp = self.timestamp.max(event.timestamp);
+
+        let events = self.events;
+        self.resolve(|horizon, pending| match horizon {
+            Horizon::Time(_) => false,
+            Horizon::Events(n) => events >= pending.event + n,
+        });
+
+        self.drain(false)
+    }
+
+    /// Ends the replay, returning every marked state left, with the horizons that did not pass
+    /// left empty.
+    pub fn finish(mut self) -> Vec<Labeled<T>> {
+        let now = self.timestamp;
+
+        // Events are complete up to the last timestamp.
+        self.resolve(|horizon, pending| match horizon {
+            Horizon::Time(span) => now >= pending.timestamp + span,
+            Horizon::Events(_) => false,
+        });
+
+        self.drain(true)
+    }
+
+    fn resolve(&mut self, passed: impl Fn(Horizon, &Pending<T>) -> bool) {
+        let midprice = self.book.midprice();
+
+        for (i, &horizon) in self.horizons.iter().enumerate() {
+            let cursor = &mut self.cursors[i];
+
+            while let Some(pending) = self.pending.get_mut(*cursor - self.head) {
+                if !passed(horizon, pending) {
+                    break;
+                }
+
+                pending.returns[i] = pending
+                    .midprice
+                    .zip(midprice)
+                    .map(|(start, end)| end / start - 1.0);
+                *cursor += 1;
+            }
+        }
+    }
+
+    fn drain(&mut self, all: bool) -> Vec<Labeled<T>> {
+        let resolved = match (all, self.cursors.iter().min()) {
+            (false, Some(&cursor)) => cursor - self.head,
+            _ => self.pending.len(),
+        };
+
+        self.head += resolved;
+        self.cursors
+            .iter_mut()
+            .for_each(|cursor| *cursor = (*cursor).max(self.head));
+
+        let threshold = self.threshold;
+        self.pending
+            .drain(..resolved)
+            .map(|pending| Labeled {
+                classes: pending
+                    .returns
+                    .iter()
+                    .map(|r| {
+                        r.map(|r| match r {
+                            r if r > threshold => Class::Up,
+                            r if r < -threshold => Class::Down,
+                            _ => Class::Flat,
+                        })
+                    })
+                    .collect(),
+                item: pending.item,
+                timestamp: pending.timestamp,
+                midprice: pending.midprice,
+                returns: pending.returns,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(timestamp: u64, is_buy: bool, price: f64) -> Event {
+        Event {
+            timestamp,
+            seq: timestamp,
+            is_trade: false,
+            is_buy,
+            price,
+            size: 1.0,
+        }
+    }
+
+    #[test]
+    fn forward_returns() {
+        let mut labeler = Labeler::new(
+            Orderbook::new(0.01),
+            &[Horizon::Events(1), Horizon::Time(10)],
+        )
+        .with_threshold(0.001);
+
+        assert!(labeler.process(quote(1, true, 99.0)).is_empty());
+        assert!(labeler.process(quote(2, false, 101.0)).is_empty());
+
+        labeler.mark("a");
+
+        // One event later the midprice is up 1%, and 2% once 10 microseconds have passed.
+        assert!(labeler.process(quote(5, true, 101.0)).is_empty());
+        assert!(labeler.process(quote(10, false, 103.0)).is_empty());
+        assert!(labeler
+            .process(Event {
+                size: 0.0,
+                ..quote(12, false, 101.0)
+            })
+            .is_empty());
+
+        let labeled = labeler.process(quote(13, true, 100.0));
+
+        assert_eq!(labeled.len(), 1);
+        assert_eq!(labeled[0].item, "a");
+        assert_eq!(labeled[0].midprice, Some(100.0));
+        assert!((labeled[0].returns[0].unwrap() - 0.01).abs() < 1e-12);
+        assert!((labeled[0].returns[1].unwrap() - 0.02).abs() < 1e-12);
+        assert_eq!(labeled[0].classes, [Some(Class::Up), Some(Class::Up)]);
+
+        labeler.mark("b");
+        assert!(labeler.process(quote(14, true, 99.0)).is_empty());
+
+        let labeled = labeler.finish();
+
+        assert_eq!(labeled[0].item, "b");
+        assert_eq!(labeled[0].returns[1], None);
+        assert_eq!(labeled[0].classes[0], Some(Class::Flat));
+    }
+}
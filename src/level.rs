@@ -2,6 +2,9 @@ use crate::event::Event;
 use std::cmp::Ordering;
 use std::fmt::Display;
 
+/// Best bid and best ask of a book.
+pub type Bbo = (Option<Level>, Option<Level>);
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Level {
     pub price: f64,
@@ -1,5 +1,31 @@
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(test)]
+mod backend_tests;
+pub mod bars;
+pub mod binance;
+pub mod coinbase;
+pub mod config;
+pub mod dbn;
+pub mod depth;
 pub mod event;
+pub mod fix;
 pub mod fixed_orderbook;
+pub mod grouped;
+pub mod heatmap;
+pub mod itch;
+pub mod l3_orderbook;
+pub mod labels;
 pub mod level;
+pub mod metrics;
 pub mod naive_orderbook;
+pub mod npy;
+pub mod ofi;
 pub mod orderbook;
+pub mod sampler;
+pub mod schema;
+pub mod simulation;
+pub mod tape;
+pub mod tardis;
+pub mod tensor;
+mod time;
@@ -0,0 +1,396 @@
+use crate::{event::Event, level::Level, orderbook::Orderbook};
+
+/// Stoikov's microprice, estimated from the observed dynamics of the best levels.
+///
+/// The state of the book is the imbalance at the touch, bucketed, and the spread in ticks. After
+/// [`Microprice::observe`] has seen enough best levels, [`Microprice::fit`] computes the expected
+/// long-run change of the midprice from every state, which is added to the midprice.
+#[derive(Debug, Clone)]
+pub struct Microprice {
+    tick_size: f64,
+    buckets: usize,
+    max_spread: usize,
+    stay: Vec<f64>,
+    jump: Vec<f64>,
+    drift: Vec<f64>,
+    totals: Vec<f64>,
+    last: Option<(usize, f64)>,
+    adjustments: Vec<f64>,
+}
+
+impl Microprice {
+    /// Models spreads of 1 to `max_spread` ticks with the imbalance split into `buckets`.
+    pub fn new(tick_size: f64, buckets: usize, max_spread: usize) -> Self {
+        assert!(tick_size > 0.0, "tick size must be positive");
+        assert!(buckets > 0, "the imbalance needs at least one bucket");
+        assert!(
+            max_spread > 0,
+            "the maximum spread must be at least one tick"
+        );
+
+        let states = buckets * max_spread;
+
+        Self {
+            tick_size,
+            buckets,
+            max_spread,
+            stay: vec![0.0; states * states],
+            jump: vec![0.0; states * states],
+            drift: vec![0.0; states],
+            totals: vec![0.0; states],
+            last: None,
+            adjustments: vec![0.0; states],
+        }
+    }
+
+    fn states(&self) -> usize {
+        self.buckets * self.max_spread
+    }
+
+    fn state(&self, bid: Level, ask: Level) -> Option<usize> {
+        let total = bid.size + ask.size;
+        if total <= 0.0 {
+            return None;
+        }
+
+        let spread = ((ask.price - bid.price) / self.tick_size).round();
+        if spread < 1.0 || spread > self.max_spread as f64 {
+            return None;
+        }
+
+        let bucket = ((bid.size / total * self.buckets as f64) as usize).min(self.buckets - 1);
+
+        Some((spread as usize - 1) * self.buckets + bucket)
+    }
+
+    /// The same state with the sides swapped.
+    fn mirror(&self, state: usize) -> usize {
+        let (spread, bucket) = (state / self.buckets, state % self.buckets);
+        spread * self.buckets + self.buckets - 1 - bucket
+    }
+
+    fn record(&mut self, from: usize, to: usize, change: f64) {
+        let states = self.states();
+
+        if change == 0.0 {
+            self.stay[from * states + to] += 1.0;
+        } else {
+            self.jump[from * states + to] += 1.0;
+            self.drift[from] += change;
+        }
+        self.totals[from] += 1.0;
+    }
+
+    /// Records the best levels of a book, sampled at a regular interval or on every change.
+    pub fn observe(&mut self, bid: Level, ask: Level) {
+        let Some(state) = self.state(bid, ask) else {
+            self.last = None;
+            return;
+        };
+        let mid = (bid.price + ask.price) / 2.0;
+
+        if let Some((last, last_mid)) = self.last {
+            let change = mid - last_mid;
+            self.record(last, state, change);
+            self.record(self.mirror(last), self.mirror(state), -change);
+        }
+
+        self.last = Some((state, mid));
+    }
+
+    /// Estimates the adjustments from everything observed so far.
+    pub fn fit(&mut self) {
+        let states = self.states();
+        let width = states + 1 + states;
+
+        // [I - Q | R | T], with rows of states never seen left empty.
+        let mut matrix = vec![0.0; states * width];
+        for from in 0..states {
+            let row = &mut matrix[from * width..(from + 1) * width];
+            row[from] = 1.0;
+
+            let total = self.totals[from];
+            if total == 0.0 {
+                continue;
+            }
+
+            for to in 0..states {
+                row[to] -= self.stay[from * states + to] / total;
+                row[states + 1 + to] = self.jump[from * states + to] / total;
+            }
+            row[states] = self.drift[from] / total;
+        }
+
+        let solved = solve(&mut matrix, states, width);
+        let columns = width - states;
+
+        // G = g1 + B g1 + B^2 g1 + ...
+        let mut term: Vec<f64> = (0..states).map(|x| solved[x * columns]).collect();
+        let mut adjustments = term.clone();
+        for _ in 0..20 {
+            term = (0..states)
+                .map(|x| {
+                    (0..states)
+                        .map(|y| solved[x * columns + 1 + y] * term[y])
+                        .sum()
+                })
+                .collect();
+
+            adjustments
+                .iter_mut()
+                .zip(&term)
+                .for_each(|(adjustment, t)| *adjustment += t);
+
+            if term.iter().all(|t| t.abs() < 1e-12) {
+                break;
+            }
+        }
+
+        self.adjustments = adjustments;
+    }
+
+    /// Expected long-run change of the midprice, or `None` for a state outside the model.
+    pub fn adjustment(&self, bid: Level, ask: Level) -> Option<f64> {
+        Some(self.adjustments[self.state(bid, ask)?])
+    }
+
+    pub fn microprice(&self, bid: Level, ask: Level) -> Option<f64> {
+        Some((bid.price + ask.price) / 2.0 + self.adjustment(bid, ask)?)
+    }
+}
+
+/// Gauss-Jordan elimination of the square left part of `matrix` over the columns on its right.
+///
+/// Returns the solutions, one row per state, which are 0 where the system is singular.
+fn solve(matrix: &mut [f64], rows: usize, width: usize) -> Vec<f64> {
+    let columns = width - rows;
+    let mut solutions = vec![0.0; rows * columns];
+    let mut used = vec![false; rows];
+    let mut pivots = vec![None; rows];
+
+    for col in 0..rows {
+        let Some(pivot) = (0..rows)
+            .filter(|&row| !used[row])
+            .max_by(|&a, &b| {
+                matrix[a * width + col]
+                    .abs()
+                    .total_cmp(&matrix[b * width + col].abs())
+            })
+            .filter(|&row| matrix[row * width + col].abs() > 1e-12)
+        else {
+            continue;
+        };
+        used[pivot] = true;
+        pivots[col] = Some(pivot);
+
+        let value = matrix[pivot * width + col];
+        for row in 0..rows {
+            if row == pivot || matrix[row * width + col] == 0.0 {
+                continue;
+            }
+
+            let factor = matrix[row * width + col] / value;
+            for i in 0..width {
+                matrix[row * width + i] -= factor * matrix[pivot * width + i];
+            }
+        }
+    }
+
+    for (col, pivot) in pivots.into_iter().enumerate() {
+        let Some(pivot) = pivot else {
+            continue;
+        };
+
+        let value = matrix[pivot * width + col];
+        for c in 0..columns {
+            solutions[col * columns + c] = matrix[pivot * width + rows + c] / value;
+        }
+    }
+
+    solutions
+}
+
+/// Book metrics kept up to date as events are processed.
+///
+/// The metrics are only recomputed when an event can change the levels they look at.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    book: Orderbook,
+    levels: usize,
+    decay: f64,
+    model: Option<Microprice>,
+    imbalance: Option<f64>,
+    weighted_imbalance: Option<f64>,
+    multi_level_midprice: Option<f64>,
+    microprice: Option<f64>,
+}
+
+impl Metrics {
+    /// Tracks the metrics over the top `levels` levels of each side.
+    pub fn new(book: Orderbook, levels: usize) -> Self {
+        let mut metrics = Self {
+            book,
+            levels: levels.max(1),
+            decay: 1.0,
+            model: None,
+            imbalance: None,
+            weighted_imbalance: None,
+            multi_level_midprice: None,
+            microprice: None,
+        };
+        metrics.update();
+        metrics
+    }
+
+    /// Sets the weight of each level relative to the one before it in the weighted imbalance.
+    pub fn with_decay(mut self, decay: f64) -> Self {
+        self.decay = decay;
+        self.update();
+        self
+    }
+
+    pub fn with_microprice(mut self, model: Microprice) -> Self {
+        self.model = Some(model);
+        self.update();
+        self
+    }
+
+    pub fn book(&self) -> &Orderbook {
+        &self.book
+    }
+
+    pub fn into_inner(self) -> Orderbook {
+        self.book
+    }
+
+    /// Processes an event, returning whether the metrics were recomputed.
+    pub fn process(&mut self, event: Event) -> bool {
+        let touches = event.is_trade || {
+            let edge = match event.is_buy {
+                true => self.book.bids().nth(self.levels - 1),
+                false => self.book.asks().nth(self.levels - 1),
+            };
+
+            match (edge, event.is_buy) {
+                (None, _) => true,
+                (Some(edge), true) => event.price >= edge.price,
+                (Some(edge), false) => event.price <= edge.price,
+            }
+        };
+
+        self.book.process(event);
+
+        if touches {
+            self.update();
+        }
+        touches
+    }
+
+    fn update(&mut self) {
+        self.imbalance = self.book.imbalance(self.levels);
+        self.weighted_imbalance = self.book.weighted_imbalance(self.levels, self.decay);
+        self.multi_level_midprice = self.book.multi_level_midprice(self.levels);
+        self.microprice = self
+            .model
+            .as_ref()
+            .and_then(|model| self.book.microprice(model));
+    }
+
+    pub fn imbalance(&self) -> Option<f64> {
+        self.imbalance
+    }
+
+    pub fn weighted_imbalance(&self) -> Option<f64> {
+        self.weighted_imbalance
+    }
+
+    pub fn multi_level_midprice(&self) -> Option<f64> {
+        self.multi_level_midprice
+    }
+
+    pub fn microprice(&self) -> Option<f64> {
+        self.microprice
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn microprice_follows_imbalance() {
+        let mut model = Microprice::new(1.0, 5, 1);
+
+        // Heavy bids make the price tick up, heavy asks make it tick down.
+        for _ in 0..50 {
+            model.observe(Level::new(99.0, 9.0), Level::new(100.0, 1.0));
+            model.observe(Level::new(100.0, 5.0), Level::new(101.0, 5.0));
+            model.observe(Level::new(100.0, 5.0), Level::new(101.0, 5.0));
+            model.observe(Level::new(100.0, 1.0), Level::new(101.0, 9.0));
+            model.observe(Level::new(99.0, 5.0), Level::new(100.0, 5.0));
+        }
+        model.fit();
+
+        let up = model.adjustment(Level::new(99.0, 9.0), Level::new(100.0, 1.0));
+        let even = model.adjustment(Level::new(99.0, 5.0), Level::new(100.0, 5.0));
+        let down = model.adjustment(Level::new(99.0, 1.0), Level::new(100.0, 9.0));
+
+        assert!(up.unwrap() > 0.0);
+        assert!(even.unwrap().abs() < 1e-9);
+        assert!((up.unwrap() + down.unwrap()).abs() < 1e-9);
+        assert_eq!(
+            model.microprice(Level::new(99.0, 5.0), Level::new(100.0, 5.0)),
+            Some(99.5)
+        );
+        assert_eq!(
+            model.adjustment(Level::new(99.0, 5.0), Level::new(102.0, 5.0)),
+            None
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one bucket")]
+    fn microprice_without_buckets() {
+        Microprice::new(1.0, 0, 1);
+    }
+
+    #[test]
+    fn metrics_update_on_top_levels() {
+        let mut metrics = Metrics::new(Orderbook::new(0.01), 2);
+
+        let event = Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 99.0,
+            size: 3.0,
+        };
+
+        assert!(metrics.process(event));
+        assert!(metrics.process(Event {
+            seq: 1,
+            is_buy: false,
+            price: 101.0,
+            size: 1.0,
+            ..event
+        }));
+        assert!(metrics.process(Event {
+            seq: 2,
+            price: 98.0,
+            size: 1.0,
+            ..event
+        }));
+
+        assert_eq!(metrics.imbalance(), Some(0.6));
+        assert_eq!(metrics.imbalance(), metrics.book().imbalance(2));
+
+        assert!(!metrics.process(Event {
+            seq: 3,
+            price: 90.0,
+            size: 10.0,
+            ..event
+        }));
+        assert_eq!(metrics.imbalance(), Some(0.6));
+    }
+}
@@ -1,4 +1,8 @@
-use crate::{event::Event, level::Level};
+use crate::{
+    config::{TradeMode, UpdateMode},
+    event::Event,
+    level::Level,
+};
 
 /// Naive implementation of an orderbook to use as a benchmark
 /// This has no use other than benchmarking.
@@ -10,6 +14,9 @@ pub struct Orderbook {
     asks: Vec<Level>,
     last_updated: u64,
     last_sequence: u64,
+    update_mode: UpdateMode,
+    trade_mode: TradeMode,
+    sweep: bool,
 }
 
 impl Orderbook {
@@ -21,9 +28,28 @@ impl Orderbook {
             asks: Vec::new(),
             last_updated: 0,
             last_sequence: 0,
+            update_mode: UpdateMode::Absolute,
+            trade_mode: TradeMode::Maker,
+            sweep: false,
         }
     }
 
+    pub fn with_update_mode(mut self, update_mode: UpdateMode) -> Self {
+        self.update_mode = update_mode;
+        self
+    }
+
+    pub fn with_trade_mode(mut self, trade_mode: TradeMode) -> Self {
+        self.trade_mode = trade_mode;
+        self
+    }
+
+    /// Makes a trade also remove every level on its side priced better than the trade.
+    pub fn with_sweep(mut self, sweep: bool) -> Self {
+        self.sweep = sweep;
+        self
+    }
+
     pub fn process(&mut self, event: Event) {
         if event.timestamp < self.last_updated || event.seq < self.last_sequence {
             return;
@@ -55,6 +81,7 @@ impl Orderbook {
     }
 
     fn process_lvl2(&mut self, event: Event) {
+        let event = self.to_absolute(event);
         match event.is_buy {
             true => {
                 if event.size == 0.0 {
@@ -95,12 +122,46 @@ impl Orderbook {
         }
     }
 
+    fn to_absolute(&self, event: Event) -> Event {
+        match self.update_mode {
+            UpdateMode::Absolute => event,
+            UpdateMode::Delta => {
+                let levels = match event.is_buy {
+                    true => &self.bids,
+                    false => &self.asks,
+                };
+                let current = levels
+                    .iter()
+                    .find(|x| x.price == event.price)
+                    .map_or(0.0, |level| level.size);
+
+                Event {
+                    size: (current + event.size).max(0.0),
+                    ..event
+                }
+            }
+        }
+    }
+
     fn process_trade(&mut self, event: Event) {
-        let buf = match event.is_buy {
+        let is_bid = match self.trade_mode {
+            TradeMode::Maker => event.is_buy,
+            TradeMode::Aggressor => !event.is_buy,
+            TradeMode::Ignore => return,
+        };
+
+        let buf = match is_bid {
             true => &mut self.bids,
             false => &mut self.asks,
         };
 
+        if self.sweep {
+            match is_bid {
+                true => buf.retain(|x| x.price <= event.price),
+                false => buf.retain(|x| x.price >= event.price),
+            }
+        }
+
         if let Some(index) = buf.iter().position(|x| x.price == event.price) {
             let level = buf.get_mut(index).unwrap();
             if event.size >= level.size {
@@ -109,6 +170,11 @@ impl Orderbook {
                 level.size -= event.size;
             }
         };
+
+        match is_bid {
+            true => self.best_bid = self.bids.last().cloned(),
+            false => self.best_ask = self.asks.first().cloned(),
+        }
     }
 
     pub fn best_bid(&self) -> Option<Level> {
@@ -127,6 +193,46 @@ impl Orderbook {
         self.asks.iter().take(n).cloned().collect()
     }
 
+    /// Bids from the best price to the worst.
+    pub fn bids(&self) -> impl DoubleEndedIterator<Item = &Level> {
+        self.bids.iter().rev()
+    }
+
+    /// Asks from the best price to the worst.
+    pub fn asks(&self) -> impl DoubleEndedIterator<Item = &Level> {
+        self.asks.iter()
+    }
+
+    /// Bids priced between `low` and `high`, inclusive, from the best price.
+    pub fn bids_range(&self, low: f64, high: f64) -> impl Iterator<Item = &Level> {
+        self.bids()
+            .skip_while(move |level| level.price > high)
+            .take_while(move |level| level.price >= low)
+    }
+
+    /// Asks priced between `low` and `high`, inclusive, from the best price.
+    pub fn asks_range(&self, low: f64, high: f64) -> impl Iterator<Item = &Level> {
+        self.asks()
+            .skip_while(move |level| level.price < low)
+            .take_while(move |level| level.price <= high)
+    }
+
+    pub fn bid_at(&self, price: f64) -> Option<&Level> {
+        self.bids.iter().find(|level| level.price == price)
+    }
+
+    pub fn ask_at(&self, price: f64) -> Option<&Level> {
+        self.asks.iter().find(|level| level.price == price)
+    }
+
+    pub fn bids_len(&self) -> usize {
+        self.bids.len()
+    }
+
+    pub fn asks_len(&self) -> usize {
+        self.asks.len()
+    }
+
     pub fn midprice(&self) -> Option<f64> {
         if let (Some(best_bid), Some(best_ask)) = (self.best_bid, self.best_ask) {
             return Some((best_bid.price + best_ask.price) / 2.0);
@@ -150,6 +256,8 @@ impl Orderbook {
 mod tests {
     use super::*;
 
+    crate::backend_tests::backend_tests!(Orderbook::new());
+
     #[test]
     fn process_lvl2_bids() {
         let mut ob = Orderbook::new();
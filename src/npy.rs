@@ -0,0 +1,539 @@
+//! Writers of NumPy `.npy` and `.npz` files.
+
+use crate::{event::Event, level::Level, orderbook::Orderbook};
+use std::io::{self, Cursor, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+
+/// A value stored as one element of an array.
+pub trait Element {
+    /// NumPy type description of the element, such as `'<f8'`.
+    fn descr() -> String;
+
+    /// Appends the little endian bytes of the element.
+    fn write(&self, out: &mut Vec<u8>);
+}
+
+macro_rules! impl_element {
+    ($($ty:ty => $descr:literal),*) => {
+        $(
+            impl Element for $ty {
+                fn descr() -> String {
+                    $descr.to_string()
+                }
+
+                fn write(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_element!(f32 => "'<f4'", f64 => "'<f8'", u64 => "'<u8'", i64 => "'<i8'");
+
+impl Element for bool {
+    fn descr() -> String {
+        "'|b1'".to_string()
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl Element for Level {
+    fn descr() -> String {
+        "[('price', '<f8'), ('size', '<f8')]".to_string()
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        self.price.write(out);
+        self.size.write(out);
+    }
+}
+
+impl Element for Event {
+    fn descr() -> String {
+        "[('timestamp', '<u8'), ('seq', '<u8'), ('is_trade', '|b1'), ('is_buy', '|b1'), \
+         ('price', '<f8'), ('size', '<f8')]"
+            .to_string()
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        self.timestamp.write(out);
+        self.seq.write(out);
+        self.is_trade.write(out);
+        self.is_buy.write(out);
+        self.price.write(out);
+        self.size.write(out);
+    }
+}
+
+/// Length of the header, padded so that the shape can be rewritten in place.
+const HEADER_LEN: usize = 256;
+
+fn header(descr: &str, shape: &[usize]) -> io::Result<Vec<u8>> {
+    let shape = match shape {
+        [dim] => format!("({dim},)"),
+        dims => format!(
+            "({})",
+            dims.iter()
+                .map(|dim| dim.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let dict = format!("{{'descr': {descr}, 'fortran_order': False, 'shape': {shape}, }}");
+
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend_from_slice(&((HEADER_LEN - 10) as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    if header.len() >= HEADER_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "array description too long for the header",
+        ));
+    }
+
+    header.resize(HEADER_LEN - 1, b' ');
+    header.push(b'\n');
+    Ok(header)
+}
+
+/// Streams an array of `T` along its first axis, which is filled in when finished.
+#[derive(Debug)]
+pub struct NpyWriter<W: Write + Seek, T: Element> {
+    writer: W,
+    shape: Vec<usize>,
+    len: usize,
+    buf: Vec<u8>,
+    marker: PhantomData<T>,
+}
+
+impl<W: Write + Seek, T: Element> NpyWriter<W, T> {
+    /// Starts an array whose rows have the given `shape`, which is empty for a 1 dimensional
+    /// array.
+    pub fn new(mut writer: W, shape: &[usize]) -> io::Result<Self> {
+        let mut full = vec![0];
+        full.extend_from_slice(shape);
+        writer.write_all(&header(&T::descr(), &full)?)?;
+
+        Ok(Self {
+            writer,
+            shape: shape.to_vec(),
+            len: 0,
+            buf: Vec::new(),
+            marker: PhantomData,
+        })
+    }
+
+    /// Appends a row, which must hold as many elements as the shape given to
+    /// [`NpyWriter::new`].
+    pub fn write(&mut self, row: &[T]) -> io::Result<()> {
+        if row.len() != self.shape.iter().product::<usize>() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("row of {} elements for shape {:?}", row.len(), self.shape),
+            ));
+        }
+
+        self.buf.clear();
+        row.iter().for_each(|element| element.write(&mut self.buf));
+        self.writer.write_all(&self.buf)?;
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Number of rows written.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Writes the final shape into the header and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut full = vec![self.len];
+        full.extend_from_slice(&self.shape);
+
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&header(&T::descr(), &full)?)?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+/// Writes a whole array of the given `shape` as an `.npy` file.
+pub fn write_npy<W: Write + Seek, T: Element>(
+    writer: W,
+    shape: &[usize],
+    data: &[T],
+) -> io::Result<W> {
+    let (&len, rows) = shape.split_first().unwrap_or((&0, &[]));
+    let row = rows.iter().product::<usize>();
+
+    if len * row != data.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} elements for shape {:?}", data.len(), shape),
+        ));
+    }
+
+    let mut writer = NpyWriter::new(writer, rows)?;
+    if row > 0 {
+        for chunk in data.chunks(row) {
+            writer.write(chunk)?;
+        }
+    }
+    writer.len = len;
+    writer.finish()
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let table: Vec<u32> = (0..256)
+        .map(|n| {
+            (0..8).fold(n, |c, _| match c & 1 {
+                1 => 0xedb8_8320 ^ (c >> 1),
+                _ => c >> 1,
+            })
+        })
+        .collect();
+
+    !data.iter().fold(!0, |crc, &byte| {
+        table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[derive(Debug)]
+struct ZipEntry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// Writes arrays into an uncompressed `.npz` archive.
+#[derive(Debug)]
+pub struct NpzWriter<W: Write> {
+    writer: W,
+    entries: Vec<ZipEntry>,
+    offset: u64,
+}
+
+impl<W: Write> NpzWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            entries: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    /// Adds an array of the given `shape` under `name`, without the `.npy` extension.
+    pub fn write<T: Element>(&mut self, name: &str, shape: &[usize], data: &[T]) -> io::Result<()> {
+        let bytes = write_npy(Cursor::new(Vec::new()), shape, data)?.into_inner();
+        self.write_npy(name, &bytes)
+    }
+
+    /// Adds the bytes of an `.npy` file under `name`, such as those of a finished
+    /// [`NpyWriter`].
+    pub fn write_npy(&mut self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        let name = format!("{name}.npy");
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "archive over 4 GiB");
+
+        let entry = ZipEntry {
+            crc: crc32(bytes),
+            size: u32::try_from(bytes.len()).map_err(|_| too_large())?,
+            offset: u32::try_from(self.offset).map_err(|_| too_large())?,
+            name,
+        };
+
+        let mut header = Vec::with_capacity(30 + entry.name.len());
+        header.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes());
+        entry.write_common(&mut header);
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(entry.name.as_bytes());
+
+        self.writer.write_all(&header)?;
+        self.writer.write_all(bytes)?;
+        self.offset += (header.len() + bytes.len()) as u64;
+        self.entries.push(entry);
+
+        Ok(())
+    }
+
+    /// Writes the central directory and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes());
+            entry.write_common(&mut directory);
+            // Extra field, comment, disk, internal and external attributes.
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&entry.offset.to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+
+        let count = u16::try_from(self.entries.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many arrays"))?;
+        let offset = u32::try_from(self.offset)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "archive over 4 GiB"))?;
+
+        directory.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        directory.extend_from_slice(&[0; 4]);
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&(directory.len() as u32 - 12).to_le_bytes());
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes());
+
+        self.writer.write_all(&directory)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl ZipEntry {
+    /// Fields shared by the local header and the central directory, from the flags to the length
+    /// of the name.
+    fn write_common(&self, out: &mut Vec<u8>) {
+        // Flags, stored method, time and date (1980-01-01).
+        out.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0x21, 0]);
+        out.extend_from_slice(&self.crc.to_le_bytes());
+        out.extend_from_slice(&self.size.to_le_bytes());
+        out.extend_from_slice(&self.size.to_le_bytes());
+        out.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+    }
+}
+
+/// Records the events of a replay and the state of the book after each one, to be written as
+/// an `.npz` archive.
+///
+/// Missing levels have a price of NaN and a size of 0, and missing metrics are NaN.
+#[derive(Debug, Clone)]
+pub struct BookRecorder {
+    book: Orderbook,
+    levels: usize,
+    events: Vec<Event>,
+    /// Best bid then best ask of each event.
+    bbo: Vec<Level>,
+    bids: Vec<Level>,
+    asks: Vec<Level>,
+    midprice: Vec<f64>,
+    weighted_midprice: Vec<f64>,
+    spread: Vec<f64>,
+    imbalance: Vec<f64>,
+}
+
+impl BookRecorder {
+    /// Records the top `levels` levels of each side.
+    pub fn new(book: Orderbook, levels: usize) -> Self {
+        Self {
+            book,
+            levels,
+            events: Vec::new(),
+            bbo: Vec::new(),
+            bids: Vec::new(),
+            asks: Vec::new(),
+            midprice: Vec::new(),
+            weighted_midprice: Vec::new(),
+            spread: Vec::new(),
+            imbalance: Vec::new(),
+        }
+    }
+
+    pub fn book(&self) -> &Orderbook {
+        &self.book
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn process(&mut self, event: Event) {
+        self.book.process(event);
+        self.events.push(event);
+
+        let missing = Level::new(f64::NAN, 0.0);
+        self.bbo.push(self.book.best_bid().unwrap_or(missing));
+        self.bbo.push(self.book.best_ask().unwrap_or(missing));
+
+        let levels = self.levels;
+        let pad = |levels_iter: &mut dyn Iterator<Item = &Level>, out: &mut Vec<Level>| {
+            let start = out.len();
+            out.extend(levels_iter.take(levels).copied());
+            out.resize(start + levels, missing);
+        };
+        pad(&mut self.book.bids(), &mut self.bids);
+        pad(&mut self.book.asks(), &mut self.asks);
+
+        let spread = self
+            .book
+            .best_bid()
+            .zip(self.book.best_ask())
+            .map(|(bid, ask)| ask.price - bid.price);
+
+        self.midprice.push(self.book.midprice().unwrap_or(f64::NAN));
+        self.weighted_midprice
+            .push(self.book.weighted_midprice().unwrap_or(f64::NAN));
+        self.spread.push(spread.unwrap_or(f64::NAN));
+        self.imbalance
+            .push(self.book.imbalance(levels).unwrap_or(f64::NAN));
+    }
+
+    /// Writes the arrays `events`, `timestamp`, `bbo` (best bid and ask), `bids`, `asks`,
+    /// `midprice`, `weighted_midprice`, `spread` and `imbalance`, one row per event.
+    pub fn write_npz<W: Write>(&self, writer: W) -> io::Result<W> {
+        let len = self.events.len();
+        let timestamps: Vec<u64> = self.events.iter().map(|event| event.timestamp).collect();
+
+        let mut npz = NpzWriter::new(writer);
+        npz.write("events", &[len], &self.events)?;
+        npz.write("timestamp", &[len], &timestamps)?;
+        npz.write("bbo", &[len, 2], &self.bbo)?;
+        npz.write("bids", &[len, self.levels], &self.bids)?;
+        npz.write("asks", &[len, self.levels], &self.asks)?;
+        npz.write("midprice", &[len], &self.midprice)?;
+        npz.write("weighted_midprice", &[len], &self.weighted_midprice)?;
+        npz.write("spread", &[len], &self.spread)?;
+        npz.write("imbalance", &[len], &self.imbalance)?;
+        npz.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn npy_writer() {
+        let mut writer = NpyWriter::<_, f32>::new(Cursor::new(Vec::new()), &[2]).unwrap();
+
+        writer.write(&[1.0, 2.0]).unwrap();
+        writer.write(&[3.0, 4.0]).unwrap();
+        assert!(writer.write(&[5.0]).is_err());
+
+        let bytes = writer.finish().unwrap().into_inner();
+
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        assert_eq!(u16::from_le_bytes([bytes[8], bytes[9]]), 246);
+        assert_eq!(bytes.len(), HEADER_LEN + 16);
+        assert_eq!(bytes[HEADER_LEN - 1], b'\n');
+
+        let header = std::str::from_utf8(&bytes[10..HEADER_LEN]).unwrap();
+        assert!(header.starts_with("{'descr': '<f4', 'fortran_order': False, 'shape': (2, 2), }"));
+        assert_eq!(&bytes[HEADER_LEN..HEADER_LEN + 4], 1.0f32.to_le_bytes());
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn structured_dtypes() {
+        let event = Event {
+            timestamp: 1,
+            seq: 2,
+            is_trade: true,
+            is_buy: false,
+            price: 3.0,
+            size: 4.0,
+        };
+
+        let mut out = Vec::new();
+        event.write(&mut out);
+        assert_eq!(out.len(), 34);
+        assert_eq!(out[16..18], [1, 0]);
+
+        let bytes = write_npy(Cursor::new(Vec::new()), &[1], &[Level::new(1.0, 2.0)])
+            .unwrap()
+            .into_inner();
+        let header = std::str::from_utf8(&bytes[10..HEADER_LEN]).unwrap();
+        assert!(header.contains("'descr': [('price', '<f8'), ('size', '<f8')]"));
+        assert!(header.contains("'shape': (1,)"));
+        assert_eq!(bytes.len(), HEADER_LEN + 16);
+
+        assert!(write_npy(Cursor::new(Vec::new()), &[2], &[1.0f64]).is_err());
+    }
+
+    #[test]
+    fn record_npz() {
+        let mut recorder = BookRecorder::new(Orderbook::new(0.01), 2);
+
+        let event = Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 99.0,
+            size: 1.0,
+        };
+
+        recorder.process(event);
+        recorder.process(Event {
+            seq: 1,
+            is_buy: false,
+            price: 101.0,
+            ..event
+        });
+
+        assert_eq!(recorder.len(), 2);
+        assert_eq!(recorder.midprice[1], 100.0);
+        assert!(recorder.midprice[0].is_nan());
+        assert_eq!(recorder.bids[1].size, 0.0);
+
+        let bytes = recorder.write_npz(Vec::new()).unwrap();
+
+        assert_eq!(&bytes[..4], b"PK\x03\x04");
+        assert_eq!(&bytes[30..40], b"events.npy");
+        assert_eq!(&bytes[40..46], b"\x93NUMPY");
+
+        let end = &bytes[bytes.len() - 22..];
+        assert_eq!(&end[..4], b"PK\x05\x06");
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 9);
+
+        let directory = u32::from_le_bytes(end[16..20].try_into().unwrap()) as usize;
+        let size = u32::from_le_bytes(end[12..16].try_into().unwrap()) as usize;
+        assert_eq!(directory + size, bytes.len() - 22);
+        assert_eq!(&bytes[directory..directory + 4], b"PK\x01\x02");
+    }
+
+    #[test]
+    fn record_npz_without_levels() {
+        let mut recorder = BookRecorder::new(Orderbook::new(0.01), 0);
+
+        recorder.process(Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 99.0,
+            size: 1.0,
+        });
+
+        assert_eq!(recorder.bbo[0], Level::new(99.0, 1.0));
+        assert!(recorder.bbo[1].price.is_nan());
+
+        let bytes = recorder.write_npz(Vec::new()).unwrap();
+
+        let end = &bytes[bytes.len() - 22..];
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 9);
+        assert!(bytes.windows(7).any(|name| name == b"bbo.npy"));
+    }
+}
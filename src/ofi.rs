@@ -0,0 +1,235 @@
+use crate::{event::Event, level::Level, orderbook::Orderbook};
+use std::collections::VecDeque;
+
+/// Span over which order flow imbalance is accumulated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    /// The last `n` processed events.
+    Events(usize),
+    /// Events in the last `n` microseconds.
+    Time(u64),
+}
+
+/// Order flow imbalance (Cont, Kukanov and Stoikov) of the top levels of a book, computed from
+/// the change each event makes to them.
+///
+/// Level `m` compares the `m`-th best bid and ask before and after an event: a better or equal
+/// bid adds its new size and a worse or equal bid removes its old size, and the asks count the
+/// other way round. Empty levels count as having no size at the worst possible price.
+#[derive(Debug, Clone)]
+pub struct Ofi {
+    book: Orderbook,
+    window: Window,
+    bids: Vec<Level>,
+    asks: Vec<Level>,
+    sums: Vec<f64>,
+    history: VecDeque<(u64, Vec<f64>)>,
+}
+
+impl Ofi {
+    /// Tracks the imbalance of the top `levels` levels of each side over `window`.
+    pub fn new(book: Orderbook, levels: usize, window: Window) -> Self {
+        let levels = levels.max(1);
+
+        let mut ofi = Self {
+            book,
+            window,
+            bids: vec![Level::minimum(); levels],
+            asks: vec![Level::maximum(); levels],
+            sums: vec![0.0; levels],
+            history: VecDeque::new(),
+        };
+        ofi.snapshot(&mut Vec::new());
+        ofi
+    }
+
+    pub fn book(&self) -> &Orderbook {
+        &self.book
+    }
+
+    pub fn into_inner(self) -> Orderbook {
+        self.book
+    }
+
+    /// Processes an event, returning its contribution to the best level imbalance.
+    pub fn process(&mut self, event: Event) -> f64 {
+        self.book.process(event);
+
+        let mut contributions = Vec::with_capacity(self.sums.len());
+        self.snapshot(&mut contributions);
+
+        self.sums
+            .iter_mut()
+            .zip(&contributions)
+            .for_each(|(sum, contribution)| *sum += contribution);
+
+        let best = contributions[0];
+        self.history.push_back((event.timestamp, contributions));
+        self.expire(event.timestamp);
+
+        best
+    }
+
+    /// Reads the top levels into `bids` and `asks`, pushing each level's change onto
+    /// `contributions`.
+    fn snapshot(&mut self, contributions: &mut Vec<f64>) {
+        let mut bids = self.book.bids();
+        let mut asks = self.book.asks();
+
+        for (old_bid, old_ask) in self.bids.iter_mut().zip(self.asks.iter_mut()) {
+            let bid = bids.next().copied().unwrap_or(Level::minimum());
+            let ask = asks.next().copied().unwrap_or(Level::maximum());
+
+            let mut contribution = 0.0;
+            if bid.price >= old_bid.price {
+                contribution += bid.size;
+            }
+            if bid.price <= old_bid.price {
+                contribution -= old_bid.size;
+            }
+            if ask.price <= old_ask.price {
+                contribution -= ask.size;
+            }
+            if ask.price >= old_ask.price {
+                contribution += old_ask.size;
+            }
+
+            contributions.push(contribution);
+            *old_bid = bid;
+            *old_ask = ask;
+        }
+    }
+
+    fn expire(&mut self, now: u64) {
+        while let Some((timestamp, _)) = self.history.front() {
+            let expired = match self.window {
+                Window::Events(n) => self.history.len() > n,
+                Window::Time(span) => now.saturating_sub(*timestamp) >= span,
+            };
+            if !expired {
+                break;
+            }
+
+            let (_, contributions) = self.history.pop_front().unwrap();
+            self.sums
+                .iter_mut()
+                .zip(&contributions)
+                .for_each(|(sum, contribution)| *sum -= contribution);
+        }
+
+        if self.history.is_empty() {
+            self.sums.iter_mut().for_each(|sum| *sum = 0.0);
+        }
+    }
+
+    /// Imbalance of the best levels over the window.
+    pub fn best(&self) -> f64 {
+        self.sums[0]
+    }
+
+    /// Imbalance of each of the top levels over the window, best first.
+    pub fn multi_level(&self) -> &[f64] {
+        &self.sums
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn best_level_ofi() {
+        let mut ofi = Ofi::new(Orderbook::new(0.01), 2, Window::Events(3));
+
+        let event = Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 99.0,
+            size: 2.0,
+        };
+
+        assert_eq!(ofi.process(event), 2.0);
+        assert_eq!(
+            ofi.process(Event {
+                seq: 1,
+                is_buy: false,
+                price: 101.0,
+                ..event
+            }),
+            -2.0
+        );
+
+        // A better bid adds its size, the old best becomes the second level.
+        assert_eq!(
+            ofi.process(Event {
+                seq: 2,
+                price: 100.0,
+                size: 1.0,
+                ..event
+            }),
+            1.0
+        );
+        assert_eq!(ofi.multi_level(), [1.0, 2.0]);
+
+        // The first event falls out of the window.
+        assert_eq!(
+            ofi.process(Event {
+                seq: 3,
+                is_buy: false,
+                price: 101.0,
+                size: 0.5,
+                ..event
+            }),
+            1.5
+        );
+        assert_eq!(ofi.best(), 0.5);
+        assert_eq!(ofi.multi_level(), [0.5, 2.0]);
+    }
+
+    #[test]
+    fn time_window() {
+        let mut ofi = Ofi::new(Orderbook::new(0.01), 1, Window::Time(10));
+
+        let event = Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 99.0,
+            size: 2.0,
+        };
+
+        ofi.process(event);
+        ofi.process(Event {
+            timestamp: 5,
+            seq: 1,
+            size: 3.0,
+            ..event
+        });
+        assert_eq!(ofi.best(), 3.0);
+
+        ofi.process(Event {
+            timestamp: 10,
+            seq: 2,
+            size: 1.0,
+            ..event
+        });
+        assert_eq!(ofi.best(), -1.0);
+
+        let mut ofi = Ofi::new(Orderbook::new(0.01), 1, Window::Time(u64::MAX));
+
+        ofi.process(Event {
+            timestamp: u64::MAX - 1,
+            ..event
+        });
+        ofi.process(Event {
+            timestamp: u64::MAX,
+            seq: 1,
+            size: 3.0,
+            ..event
+        });
+        assert_eq!(ofi.best(), 3.0);
+    }
+}
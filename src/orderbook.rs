@@ -1,4 +1,12 @@
-use crate::{event::Event, level::Level};
+use crate::{
+    config::{TradeMode, UpdateMode},
+    depth::{DepthCurve, DepthPoint},
+    event::Event,
+    grouped::Grouped,
+    level::Level,
+    metrics::Microprice,
+    simulation::{walk, Amount, Execution},
+};
 use std::collections::BTreeMap;
 
 #[derive(Debug, Default, Clone)]
@@ -10,6 +18,9 @@ pub struct Orderbook {
     last_updated: u64,
     last_sequence: u64,
     inv_tick_size: f64,
+    update_mode: UpdateMode,
+    trade_mode: TradeMode,
+    sweep: bool,
 }
 
 impl Orderbook {
@@ -22,9 +33,28 @@ impl Orderbook {
             last_updated: 0,
             last_sequence: 0,
             inv_tick_size: 1.0 / tick_size,
+            update_mode: UpdateMode::Absolute,
+            trade_mode: TradeMode::Maker,
+            sweep: false,
         }
     }
 
+    pub fn with_update_mode(mut self, update_mode: UpdateMode) -> Self {
+        self.update_mode = update_mode;
+        self
+    }
+
+    pub fn with_trade_mode(mut self, trade_mode: TradeMode) -> Self {
+        self.trade_mode = trade_mode;
+        self
+    }
+
+    /// Makes a trade also remove every level on its side priced better than the trade.
+    pub fn with_sweep(mut self, sweep: bool) -> Self {
+        self.sweep = sweep;
+        self
+    }
+
     #[inline]
     pub fn process_raw(
         &mut self,
@@ -104,6 +134,7 @@ impl Orderbook {
     #[inline]
     fn process_lvl2(&mut self, event: Event) {
         let price_ticks = event.price_ticks(self.inv_tick_size);
+        let event = self.to_absolute(event, price_ticks);
         match event.is_buy {
             true => {
                 if event.size == 0.0 {
@@ -158,15 +189,48 @@ impl Orderbook {
         }
     }
 
+    /// Turns a delta update into the new absolute size of its level.
+    #[inline]
+    fn to_absolute(&self, event: Event, price_ticks: u64) -> Event {
+        match self.update_mode {
+            UpdateMode::Absolute => event,
+            UpdateMode::Delta => {
+                let levels = match event.is_buy {
+                    true => &self.bids,
+                    false => &self.asks,
+                };
+                let current = levels.get(&price_ticks).map_or(0.0, |level| level.size);
+
+                Event {
+                    size: (current + event.size).max(0.0),
+                    ..event
+                }
+            }
+        }
+    }
+
     #[inline]
     fn process_trade(&mut self, event: Event) {
-        let buf = match event.is_buy {
-            true => &mut self.bids,
-            false => &mut self.asks,
+        let is_bid = match self.trade_mode {
+            TradeMode::Maker => event.is_buy,
+            TradeMode::Aggressor => !event.is_buy,
+            TradeMode::Ignore => return,
         };
 
         let price_ticks = event.price_ticks(self.inv_tick_size);
 
+        let (buf, best) = match is_bid {
+            true => (&mut self.bids, &mut self.best_bid),
+            false => (&mut self.asks, &mut self.best_ask),
+        };
+
+        if self.sweep {
+            match is_bid {
+                true => drop(buf.split_off(&(price_ticks + 1))),
+                false => *buf = buf.split_off(&price_ticks),
+            }
+        }
+
         if let Some(level) = buf.get_mut(&price_ticks) {
             if event.size >= level.size {
                 buf.remove(&price_ticks);
@@ -174,6 +238,21 @@ impl Orderbook {
                 level.size -= event.size;
             }
         };
+
+        *best = match is_bid {
+            true => buf.values().next_back().cloned(),
+            false => buf.values().next().cloned(),
+        };
+    }
+
+    /// Removes every level from the book, keeping its configuration.
+    pub fn clear(&mut self) {
+        self.best_bid = None;
+        self.best_ask = None;
+        self.bids.clear();
+        self.asks.clear();
+        self.last_updated = 0;
+        self.last_sequence = 0;
     }
 
     pub fn best_bid(&self) -> Option<Level> {
@@ -194,6 +273,62 @@ impl Orderbook {
         self.asks.values().take(n).cloned().collect()
     }
 
+    /// Bids from the best price to the worst.
+    pub fn bids(&self) -> impl DoubleEndedIterator<Item = &Level> {
+        self.bids.values().rev()
+    }
+
+    /// Asks from the best price to the worst.
+    pub fn asks(&self) -> impl DoubleEndedIterator<Item = &Level> {
+        self.asks.values()
+    }
+
+    /// Bids priced between `low` and `high`, inclusive, from the best price.
+    pub fn bids_range(&self, low: f64, high: f64) -> impl Iterator<Item = &Level> {
+        self.range(&self.bids, low, high).rev()
+    }
+
+    /// Asks priced between `low` and `high`, inclusive, from the best price.
+    pub fn asks_range(&self, low: f64, high: f64) -> impl Iterator<Item = &Level> {
+        self.range(&self.asks, low, high)
+    }
+
+    pub fn bid_at(&self, price: f64) -> Option<&Level> {
+        self.bids.get(&((price * self.inv_tick_size) as u64))
+    }
+
+    pub fn ask_at(&self, price: f64) -> Option<&Level> {
+        self.asks.get(&((price * self.inv_tick_size) as u64))
+    }
+
+    pub fn bids_len(&self) -> usize {
+        self.bids.len()
+    }
+
+    pub fn asks_len(&self) -> usize {
+        self.asks.len()
+    }
+
+    /// View of the book grouped into price buckets of `increment`.
+    pub fn grouped(&self, increment: f64) -> Grouped<'_> {
+        Grouped::new(self, increment)
+    }
+
+    fn range<'a>(
+        &self,
+        levels: &'a BTreeMap<u64, Level>,
+        low: f64,
+        high: f64,
+    ) -> impl DoubleEndedIterator<Item = &'a Level> {
+        let start = (low * self.inv_tick_size) as u64;
+        let end = ((high * self.inv_tick_size) as u64).max(start);
+
+        levels
+            .range(start..=end)
+            .map(|(_, level)| level)
+            .filter(move |level| level.price >= low && level.price <= high)
+    }
+
     #[inline]
     pub fn midprice(&self) -> Option<f64> {
         if let (Some(best_bid), Some(best_ask)) = (self.best_bid, self.best_ask) {
@@ -213,12 +348,180 @@ impl Orderbook {
 
         None
     }
+
+    /// Difference of the bid and ask size over the top `n` levels, divided by their sum.
+    pub fn imbalance(&self, n: usize) -> Option<f64> {
+        self.weighted_imbalance(n, 1.0)
+    }
+
+    /// Like [`Orderbook::imbalance`], with each level weighing `decay` times the level before it.
+    pub fn weighted_imbalance(&self, n: usize, decay: f64) -> Option<f64> {
+        let weighted = |levels: &mut dyn Iterator<Item = &Level>| -> f64 {
+            levels
+                .take(n)
+                .scan(1.0, |weight, level| {
+                    let size = *weight * level.size;
+                    *weight *= decay;
+                    Some(size)
+                })
+                .sum()
+        };
+
+        let bid = weighted(&mut self.bids());
+        let ask = weighted(&mut self.asks());
+
+        (bid + ask > 0.0).then(|| (bid - ask) / (bid + ask))
+    }
+
+    /// Generalizes [`Orderbook::weighted_midprice`] to the volume weighted prices of the top `n`
+    /// levels of each side.
+    pub fn multi_level_midprice(&self, n: usize) -> Option<f64> {
+        let vwap = |levels: &mut dyn Iterator<Item = &Level>| -> Option<(f64, f64)> {
+            let (notional, size) = levels.take(n).fold((0.0, 0.0), |(notional, size), level| {
+                (notional + level.price * level.size, size + level.size)
+            });
+            (size > 0.0).then(|| (notional / size, size))
+        };
+
+        let (bid, bid_size) = vwap(&mut self.bids())?;
+        let (ask, ask_size) = vwap(&mut self.asks())?;
+
+        Some((bid_size * ask + bid * ask_size) / (bid_size + ask_size))
+    }
+
+    /// Stoikov's microprice of the best levels under a fitted `model`.
+    pub fn microprice(&self, model: &Microprice) -> Option<f64> {
+        model.microprice(self.best_bid?, self.best_ask?)
+    }
+
+    /// Total bid size priced between `low` and `high`, inclusive.
+    pub fn bid_depth_between(&self, low: f64, high: f64) -> f64 {
+        self.bids_range(low, high).map(|level| level.size).sum()
+    }
+
+    /// Total ask size priced between `low` and `high`, inclusive.
+    pub fn ask_depth_between(&self, low: f64, high: f64) -> f64 {
+        self.asks_range(low, high).map(|level| level.size).sum()
+    }
+
+    /// Total bid size within `bps` basis points below the midprice.
+    pub fn bid_depth_within_bps(&self, bps: f64) -> Option<f64> {
+        let mid = self.midprice()?;
+        let (low, _) = bps_bounds(mid, bps);
+        Some(self.bid_depth_between(low, mid))
+    }
+
+    /// Total ask size within `bps` basis points above the midprice.
+    pub fn ask_depth_within_bps(&self, bps: f64) -> Option<f64> {
+        let mid = self.midprice()?;
+        let (_, high) = bps_bounds(mid, bps);
+        Some(self.ask_depth_between(mid, high))
+    }
+
+    /// Cumulative bid and ask size and notional out to `bps` basis points from the midprice.
+    pub fn depth_curve(&self, bps: f64) -> Option<DepthCurve> {
+        let mid = self.midprice()?;
+        let (low, high) = bps_bounds(mid, bps);
+
+        Some(DepthCurve {
+            midprice: mid,
+            bids: cumulative(self.bids_range(low, mid)).collect(),
+            asks: cumulative(self.asks_range(mid, high)).collect(),
+        })
+    }
+
+    /// Bids from the best price, each with the total size up to and including it.
+    pub fn cumulative_bids(&self) -> impl Iterator<Item = Level> + '_ {
+        cumulative(self.bids.values().rev()).map(|point| Level::new(point.price, point.size))
+    }
+
+    /// Asks from the best price, each with the total size up to and including it.
+    pub fn cumulative_asks(&self) -> impl Iterator<Item = Level> + '_ {
+        cumulative(self.asks.values()).map(|point| Level::new(point.price, point.size))
+    }
+
+    /// Price of the bid at which the cumulative size reaches `size`.
+    pub fn bid_price_for_size(&self, size: f64) -> Option<f64> {
+        price_reached(self.bids.values().rev(), size, |level| level.size)
+    }
+
+    /// Price of the ask at which the cumulative size reaches `size`.
+    pub fn ask_price_for_size(&self, size: f64) -> Option<f64> {
+        price_reached(self.asks.values(), size, |level| level.size)
+    }
+
+    /// Price of the bid at which the cumulative notional reaches `notional`.
+    pub fn bid_price_for_notional(&self, notional: f64) -> Option<f64> {
+        price_reached(self.bids.values().rev(), notional, |level| {
+            level.price * level.size
+        })
+    }
+
+    /// Price of the ask at which the cumulative notional reaches `notional`.
+    pub fn ask_price_for_notional(&self, notional: f64) -> Option<f64> {
+        price_reached(self.asks.values(), notional, |level| {
+            level.price * level.size
+        })
+    }
+
+    /// Fills `quantity` against the opposite side of the book without changing it.
+    pub fn simulate_market_order(&self, is_buy: bool, quantity: f64) -> Execution {
+        self.simulate(is_buy, Amount::Quantity(quantity))
+    }
+
+    /// Like [`Orderbook::simulate_market_order`], but spends `notional` in quote currency.
+    pub fn simulate_market_order_notional(&self, is_buy: bool, notional: f64) -> Execution {
+        self.simulate(is_buy, Amount::Notional(notional))
+    }
+
+    fn simulate(&self, is_buy: bool, amount: Amount) -> Execution {
+        match is_buy {
+            true => walk(self.asks.values(), is_buy, amount, self.midprice()),
+            false => walk(self.bids.values().rev(), is_buy, amount, self.midprice()),
+        }
+    }
+}
+
+/// Prices `bps` basis points below and above `midprice`.
+pub(crate) fn bps_bounds(midprice: f64, bps: f64) -> (f64, f64) {
+    (
+        midprice * (1.0 - bps / 10_000.0),
+        midprice * (1.0 + bps / 10_000.0),
+    )
+}
+
+/// Levels with the total size and notional up to and including each of them.
+pub(crate) fn cumulative<'a>(
+    levels: impl Iterator<Item = &'a Level> + 'a,
+) -> impl Iterator<Item = DepthPoint> + 'a {
+    levels.scan(DepthPoint::default(), |total, level| {
+        total.price = level.price;
+        total.size += level.size;
+        total.notional += level.price * level.size;
+        Some(*total)
+    })
+}
+
+fn price_reached<'a>(
+    mut levels: impl Iterator<Item = &'a Level>,
+    target: f64,
+    amount: impl Fn(&Level) -> f64,
+) -> Option<f64> {
+    let mut total = 0.0;
+    levels
+        .find(|level| {
+            total += amount(level);
+            total >= target
+        })
+        .map(|level| level.price)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    crate::backend_tests::backend_tests!(Orderbook::new(0.01));
+
     #[test]
     fn process_lvl2_bids() {
         let mut ob = Orderbook::new(0.01);
@@ -1132,4 +1435,177 @@ mod tests {
 
         assert_eq!(weighted_midprice, 16.8)
     }
+
+    #[test]
+    fn clear() {
+        let mut ob = Orderbook::new(0.01);
+
+        let event = Event {
+            timestamp: 10,
+            seq: 10,
+            is_trade: false,
+            is_buy: true,
+            price: 16.0,
+            size: 1.0,
+        };
+
+        ob.process(event);
+
+        ob.clear();
+
+        assert_eq!(ob.best_bid(), None);
+        assert!(ob.top_bids(5).is_empty());
+
+        let event = Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: false,
+            is_buy: false,
+            price: 20.0,
+            size: 1.0,
+        };
+
+        ob.process(event);
+
+        assert_eq!(
+            ob.best_ask().unwrap(),
+            Level {
+                price: 20.0,
+                size: 1.0
+            }
+        );
+    }
+
+    #[test]
+    fn simulate_market_order() {
+        let mut ob = Orderbook::new(0.01);
+
+        [(true, 99.0), (false, 101.0), (false, 102.0)]
+            .into_iter()
+            .enumerate()
+            .for_each(|(i, (is_buy, price))| {
+                ob.process(Event {
+                    timestamp: 0,
+                    seq: i as u64,
+                    is_trade: false,
+                    is_buy,
+                    price,
+                    size: 2.0,
+                })
+            });
+
+        let execution = ob.simulate_market_order(true, 3.0);
+
+        assert_eq!(
+            execution.fills,
+            [
+                Level {
+                    price: 101.0,
+                    size: 2.0
+                },
+                Level {
+                    price: 102.0,
+                    size: 1.0
+                },
+            ]
+        );
+        assert_eq!(execution.notional, 304.0);
+        assert_eq!(execution.worst_price, Some(102.0));
+        assert_eq!(execution.unfilled, 0.0);
+        assert!((execution.slippage.unwrap() - (304.0 / 3.0 - 100.0)).abs() < 1e-9);
+
+        let execution = ob.simulate_market_order(false, 5.0);
+
+        assert_eq!(execution.filled, 2.0);
+        assert_eq!(execution.average_price, Some(99.0));
+        assert_eq!(execution.slippage, Some(1.0));
+        assert_eq!(execution.unfilled, 3.0);
+
+        let execution = ob.simulate_market_order_notional(true, 253.0);
+
+        assert_eq!(execution.filled, 2.5);
+        assert_eq!(execution.worst_price, Some(102.0));
+        assert_eq!(execution.unfilled, 0.0);
+
+        assert_eq!(ob.top_asks(5).len(), 2);
+    }
+
+    #[test]
+    fn depth_queries() {
+        let mut ob = Orderbook::new(0.01);
+
+        [
+            (true, 99.0, 1.0),
+            (true, 98.0, 2.0),
+            (true, 97.0, 3.0),
+            (false, 101.0, 1.0),
+            (false, 102.0, 2.0),
+        ]
+        .into_iter()
+        .enumerate()
+        .for_each(|(i, (is_buy, price, size))| {
+            ob.process(Event {
+                timestamp: 0,
+                seq: i as u64,
+                is_trade: false,
+                is_buy,
+                price,
+                size,
+            })
+        });
+
+        assert_eq!(ob.bid_depth_between(97.5, 99.0), 3.0);
+        assert_eq!(ob.bid_depth_between(99.0, 97.5), 0.0);
+        assert_eq!(ob.ask_depth_between(100.0, 200.0), 3.0);
+
+        assert_eq!(ob.bid_depth_within_bps(150.0), Some(1.0));
+        assert_eq!(ob.ask_depth_within_bps(250.0), Some(3.0));
+
+        assert_eq!(
+            ob.cumulative_bids().collect::<Vec<_>>(),
+            [
+                Level::new(99.0, 1.0),
+                Level::new(98.0, 3.0),
+                Level::new(97.0, 6.0)
+            ]
+        );
+
+        assert_eq!(ob.bid_price_for_size(2.0), Some(98.0));
+        assert_eq!(ob.bid_price_for_size(7.0), None);
+        assert_eq!(ob.ask_price_for_size(1.0), Some(101.0));
+        assert_eq!(ob.ask_price_for_notional(102.0), Some(102.0));
+        assert_eq!(ob.bid_price_for_notional(99.0), Some(99.0));
+    }
+
+    #[test]
+    fn imbalance() {
+        let mut ob = Orderbook::new(0.01);
+
+        assert_eq!(ob.imbalance(5), None);
+
+        [
+            (true, 99.0, 3.0),
+            (true, 98.0, 1.0),
+            (false, 101.0, 1.0),
+            (false, 102.0, 3.0),
+        ]
+        .into_iter()
+        .enumerate()
+        .for_each(|(i, (is_buy, price, size))| {
+            ob.process(Event {
+                timestamp: 0,
+                seq: i as u64,
+                is_trade: false,
+                is_buy,
+                price,
+                size,
+            })
+        });
+
+        assert_eq!(ob.imbalance(1), Some(0.5));
+        assert_eq!(ob.imbalance(2), Some(0.0));
+        assert_eq!(ob.weighted_imbalance(2, 0.5), Some(1.0 / 6.0));
+        assert_eq!(ob.multi_level_midprice(1), ob.weighted_midprice());
+        assert_eq!(ob.multi_level_midprice(2), Some(100.25));
+    }
 }
@@ -0,0 +1,252 @@
+use crate::{event::Event, level::Level, orderbook::Orderbook};
+
+/// State of a book at a grid point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub timestamp: u64,
+    pub best_bid: Option<Level>,
+    pub best_ask: Option<Level>,
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+}
+
+impl Sample {
+    pub fn midprice(&self) -> Option<f64> {
+        let (bid, ask) = self.best_bid.zip(self.best_ask)?;
+        Some((bid.price + ask.price) / 2.0)
+    }
+}
+
+/// Samples a book on a regular clock driven by event timestamps.
+///
+/// The sample at grid point `t` holds every event with a timestamp up to and including `t`, so it
+/// is emitted once an event past `t` arrives. Grid points are multiples of the interval, starting
+/// at the first one not before the first event, and every point crossed by a gap is emitted with
+/// the book carried forward. Points of a gap are produced lazily by [`Samples`], so long gaps can
+/// be skipped without materializing them.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    book: Orderbook,
+    interval: u64,
+    levels: usize,
+    next: Option<u64>,
+}
+
+impl Sampler {
+    /// Samples the top `levels` levels every `interval` microseconds.
+    pub fn new(book: Orderbook, interval: u64, levels: usize) -> Self {
+        Self {
+            book,
+            interval: interval.max(1),
+            levels,
+            next: None,
+        }
+    }
+
+    pub fn book(&self) -> &Orderbook {
+        &self.book
+    }
+
+    pub fn into_inner(self) -> Orderbook {
+        self.book
+    }
+
+    /// Processes an event, returning the samples of the grid points before it.
+    pub fn process(&mut self, event: Event) -> Samples {
+        let samples = match event.timestamp {
+            0 => Samples::default(),
+            timestamp => self.sample_until(timestamp - 1),
+        };
+
+        if self.next.is_none() {
+            self.next = Some(event.timestamp.div_ceil(self.interval) * self.interval);
+        }
+
+        self.book.process(event);
+        samples
+    }
+
+    /// Returns the samples of the grid points up to and including `now`, once every event up to
+    /// `now` has been processed.
+    pub fn advance(&mut self, now: u64) -> Samples {
+        self.sample_until(now)
+    }
+
+    fn sample_until(&mut self, now: u64) -> Samples {
+        let Some(next) = self.next.filter(|&next| next <= now) else {
+            return Samples::default();
+        };
+
+        let remaining = (now - next) / self.interval + 1;
+        self.next = Some(next + remaining * self.interval);
+
+        Samples {
+            sample: Some(self.sample(next)),
+            interval: self.interval,
+            remaining,
+        }
+    }
+
+    fn sample(&self, timestamp: u64) -> Sample {
+        Sample {
+            timestamp,
+            best_bid: self.book.best_bid(),
+            best_ask: self.book.best_ask(),
+            bids: self.book.bids().take(self.levels).copied().collect(),
+            asks: self.book.asks().take(self.levels).copied().collect(),
+        }
+    }
+}
+
+/// Samples of consecutive grid points over which the book did not change, produced on demand.
+#[derive(Debug, Clone, Default)]
+pub struct Samples {
+    /// Sample of the next grid point.
+    sample: Option<Sample>,
+    interval: u64,
+    remaining: u64,
+}
+
+impl Samples {
+    /// Number of grid points left, which may not fit in a `usize` after a very long gap.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+impl Iterator for Samples {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.nth(0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.remaining) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        if n as u64 >= self.remaining {
+            self.remaining = 0;
+            self.sample = None;
+            return None;
+        }
+
+        let sample = self.sample.as_mut()?;
+        sample.timestamp += n as u64 * self.interval;
+        self.remaining -= n as u64 + 1;
+
+        match self.remaining {
+            0 => self.sample.take(),
+            _ => {
+                let current = sample.clone();
+                sample.timestamp += self.interval;
+                Some(current)
+            }
+        }
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        let skip = self.remaining.checked_sub(1)?;
+        let sample = self.sample.as_mut()?;
+        sample.timestamp += skip * self.interval;
+        self.sample
+    }
+
+    fn count(self) -> usize {
+        self.remaining as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carries_forward_over_gaps() {
+        let mut sampler = Sampler::new(Orderbook::new(0.01), 100, 2);
+
+        let event = Event {
+            timestamp: 150,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 99.0,
+            size: 1.0,
+        };
+
+        assert!(sampler.process(event).is_empty());
+        assert!(sampler
+            .process(Event {
+                timestamp: 200,
+                seq: 1,
+                price: 98.0,
+                ..event
+            })
+            .is_empty());
+
+        let samples: Vec<Sample> = sampler
+            .process(Event {
+                timestamp: 520,
+                seq: 2,
+                is_buy: false,
+                price: 101.0,
+                ..event
+            })
+            .collect();
+
+        assert_eq!(
+            samples.iter().map(|s| s.timestamp).collect::<Vec<_>>(),
+            [200, 300, 400, 500]
+        );
+        assert!(samples.iter().all(|s| s.bids.len() == 2
+            && s.best_bid == Some(Level::new(99.0, 1.0))
+            && s.best_ask.is_none()));
+
+        assert!(sampler.advance(599).is_empty());
+
+        let samples: Vec<Sample> = sampler.advance(600).collect();
+
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].midprice(), Some(100.0));
+    }
+
+    #[test]
+    fn long_gaps_are_lazy() {
+        let mut sampler = Sampler::new(Orderbook::new(0.01), 1, 1);
+
+        let event = Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 99.0,
+            size: 1.0,
+        };
+
+        assert!(sampler.process(event).is_empty());
+
+        let mut samples = sampler.process(Event {
+            timestamp: 1_700_000_000_000_000,
+            seq: 1,
+            ..event
+        });
+
+        assert_eq!(samples.remaining(), 1_700_000_000_000_000);
+        assert_eq!(samples.next().map(|s| s.timestamp), Some(0));
+        assert_eq!(samples.nth(9).map(|s| s.timestamp), Some(10));
+
+        let last = samples.last().unwrap();
+
+        assert_eq!(last.timestamp, 1_699_999_999_999_999);
+        assert_eq!(last.best_bid, Some(Level::new(99.0, 1.0)));
+        assert!(sampler.advance(1_699_999_999_999_999).is_empty());
+    }
+}
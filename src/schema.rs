@@ -0,0 +1,345 @@
+use crate::event::{parse_flag, parse_side, Event};
+use csv::{Reader, StringRecord};
+use std::error;
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+
+/// Column mapping used to read [`Event`]s from CSV files that don't follow the
+/// `timestamp,seq,is_trade,is_buy,price,size` layout.
+///
+/// ```
+/// use ninjabook::schema::EventSchema;
+///
+/// let data = "ts,id,trade,side,px,qty\n1,0,false,bid,10.5,2.0";
+/// let reader = csv::Reader::from_reader(data.as_bytes());
+///
+/// let schema = EventSchema::new()
+///     .timestamp("ts")
+///     .seq("id")
+///     .is_trade("trade")
+///     .side("side")
+///     .price("px")
+///     .size("qty");
+///
+/// let event = schema.reader(reader).unwrap().next().unwrap().unwrap();
+/// assert!(event.is_buy);
+/// assert_eq!(event.price, 10.5);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventSchema {
+    timestamp: String,
+    seq: String,
+    is_trade: String,
+    side: String,
+    price: String,
+    size: String,
+}
+
+impl Default for EventSchema {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventSchema {
+    pub fn new() -> Self {
+        Self {
+            timestamp: "timestamp".to_string(),
+            seq: "seq".to_string(),
+            is_trade: "is_trade".to_string(),
+            side: "is_buy".to_string(),
+            price: "price".to_string(),
+            size: "size".to_string(),
+        }
+    }
+
+    pub fn timestamp(mut self, column: &str) -> Self {
+        self.timestamp = column.to_string();
+        self
+    }
+
+    pub fn seq(mut self, column: &str) -> Self {
+        self.seq = column.to_string();
+        self
+    }
+
+    /// Column flagging trades, parsed with [`parse_flag`].
+    pub fn is_trade(mut self, column: &str) -> Self {
+        self.is_trade = column.to_string();
+        self
+    }
+
+    /// Column holding the side, either as an `is_buy` flag or as `buy`/`sell`/`bid`/`ask`,
+    /// parsed with [`parse_side`].
+    pub fn side(mut self, column: &str) -> Self {
+        self.side = column.to_string();
+        self
+    }
+
+    pub fn price(mut self, column: &str) -> Self {
+        self.price = column.to_string();
+        self
+    }
+
+    pub fn size(mut self, column: &str) -> Self {
+        self.size = column.to_string();
+        self
+    }
+
+    /// Resolves the columns against the headers of `reader`.
+    pub fn reader<R: Read>(&self, mut reader: Reader<R>) -> Result<EventReader<R>, SchemaError> {
+        let headers = reader.headers()?.clone();
+        let find = |column: &str| {
+            headers
+                .iter()
+                .position(|header| header.trim() == column)
+                .ok_or_else(|| SchemaError::MissingColumn(column.to_string()))
+        };
+
+        let columns = Columns {
+            timestamp: find(&self.timestamp)?,
+            seq: find(&self.seq)?,
+            is_trade: find(&self.is_trade)?,
+            side: find(&self.side)?,
+            price: find(&self.price)?,
+            size: find(&self.size)?,
+        };
+
+        Ok(EventReader {
+            reader,
+            record: StringRecord::new(),
+            columns,
+            schema: self.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Columns {
+    timestamp: usize,
+    seq: usize,
+    is_trade: usize,
+    side: usize,
+    price: usize,
+    size: usize,
+}
+
+/// Iterator over the [`Event`]s of a CSV file read through an [`EventSchema`].
+#[derive(Debug)]
+pub struct EventReader<R> {
+    reader: Reader<R>,
+    record: StringRecord,
+    columns: Columns,
+    schema: EventSchema,
+}
+
+impl<R: Read> EventReader<R> {
+    fn parse_record(&self) -> Result<Event, SchemaError> {
+        let row = self.record.position().map_or(0, |pos| pos.line());
+
+        let field = |index: usize, column: &str| {
+            self.record
+                .get(index)
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| SchemaError::MissingValue {
+                    row,
+                    column: column.to_string(),
+                })
+        };
+
+        let invalid = |column: &str, value: &str| SchemaError::InvalidValue {
+            row,
+            column: column.to_string(),
+            value: value.to_string(),
+        };
+
+        fn number<T: FromStr>(
+            value: &str,
+            error: impl Fn() -> SchemaError,
+        ) -> Result<T, SchemaError> {
+            value.parse().map_err(|_| error())
+        }
+
+        let schema = &self.schema;
+        let columns = self.columns;
+
+        let timestamp = field(columns.timestamp, &schema.timestamp)?;
+        let seq = field(columns.seq, &schema.seq)?;
+        let is_trade = field(columns.is_trade, &schema.is_trade)?;
+        let side = field(columns.side, &schema.side)?;
+        let price = field(columns.price, &schema.price)?;
+        let size = field(columns.size, &schema.size)?;
+
+        Ok(Event {
+            timestamp: number(timestamp, || invalid(&schema.timestamp, timestamp))?,
+            seq: number(seq, || invalid(&schema.seq, seq))?,
+            is_trade: parse_flag(is_trade).ok_or_else(|| invalid(&schema.is_trade, is_trade))?,
+            is_buy: parse_side(side).ok_or_else(|| invalid(&schema.side, side))?,
+            price: number(price, || invalid(&schema.price, price))?,
+            size: number(size, || invalid(&schema.size, size))?,
+        })
+    }
+}
+
+impl<R: Read> Iterator for EventReader<R> {
+    type Item = Result<Event, SchemaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.read_record(&mut self.record) {
+            Ok(true) => Some(self.parse_record()),
+            Ok(false) => None,
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SchemaError {
+    Csv(csv::Error),
+    /// A column of the schema is not present in the headers.
+    MissingColumn(String),
+    /// A row has an empty or missing value in one of the schema columns.
+    MissingValue {
+        row: u64,
+        column: String,
+    },
+    /// A row has a value that can't be parsed for its column.
+    InvalidValue {
+        row: u64,
+        column: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Csv(err) => write!(f, "{}", err),
+            SchemaError::MissingColumn(column) => write!(f, "missing column `{}`", column),
+            SchemaError::MissingValue { row, column } => {
+                write!(f, "row {}: missing value for column `{}`", row, column)
+            }
+            SchemaError::InvalidValue { row, column, value } => {
+                write!(
+                    f,
+                    "row {}: invalid value {:?} for column `{}`",
+                    row, value, column
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for SchemaError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SchemaError::Csv(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<csv::Error> for SchemaError {
+    fn from(value: csv::Error) -> Self {
+        SchemaError::Csv(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_schema() {
+        let data =
+            "timestamp,seq,is_trade,is_buy,price,size\n1575158405045139,0,0,1,7541.38,0.085806";
+        let reader = csv::Reader::from_reader(data.as_bytes());
+
+        let events = EventSchema::new()
+            .reader(reader)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].timestamp, 1575158405045139);
+        assert!(!events[0].is_trade);
+        assert!(events[0].is_buy);
+        assert_eq!(events[0].price, 7541.38);
+        assert_eq!(events[0].size, 0.085806);
+    }
+
+    #[test]
+    fn renamed_columns_and_sides() {
+        let data = "px,qty,side,type,ts,n\n\
+                    10.0,1.0,buy,true,1,0\n\
+                    11.0,2.0,ask,false,2,1\n\
+                    12.0,3.0,SELL,1,3,2";
+        let reader = csv::Reader::from_reader(data.as_bytes());
+
+        let schema = EventSchema::new()
+            .timestamp("ts")
+            .seq("n")
+            .is_trade("type")
+            .side("side")
+            .price("px")
+            .size("qty");
+
+        let events = schema
+            .reader(reader)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(events.len(), 3);
+        assert!(events[0].is_trade && events[0].is_buy);
+        assert!(!events[1].is_trade && !events[1].is_buy);
+        assert!(events[2].is_trade && !events[2].is_buy);
+        assert_eq!(events[2].seq, 2);
+        assert_eq!(events[2].price, 12.0);
+    }
+
+    #[test]
+    fn missing_column() {
+        let data = "timestamp,seq,is_trade,price,size\n1,0,0,10.0,1.0";
+        let reader = csv::Reader::from_reader(data.as_bytes());
+
+        let err = EventSchema::new().reader(reader).unwrap_err();
+
+        assert_eq!(err.to_string(), "missing column `is_buy`");
+    }
+
+    #[test]
+    fn invalid_value_names_row_and_value() {
+        let data = "timestamp,seq,is_trade,is_buy,price,size\n1,0,0,1,10.0,1.0\n2,1,2,1,10.0,1.0";
+        let reader = csv::Reader::from_reader(data.as_bytes());
+
+        let mut events = EventSchema::new().reader(reader).unwrap();
+
+        assert!(events.next().unwrap().is_ok());
+
+        let err = events.next().unwrap().unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "row 3: invalid value \"2\" for column `is_trade`"
+        );
+    }
+
+    #[test]
+    fn missing_value() {
+        let data = "timestamp,seq,is_trade,is_buy,price,size\n1,0,0,1,,1.0";
+        let reader = csv::Reader::from_reader(data.as_bytes());
+
+        let err = EventSchema::new()
+            .reader(reader)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "row 2: missing value for column `price`");
+    }
+}
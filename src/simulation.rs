@@ -0,0 +1,77 @@
+use crate::level::Level;
+
+/// Outcome of walking a market order through one side of a book.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Execution {
+    /// Size taken at each level, best price first.
+    pub fills: Vec<Level>,
+    /// Total base quantity filled.
+    pub filled: f64,
+    /// Total quote amount paid or received.
+    pub notional: f64,
+    pub average_price: Option<f64>,
+    pub worst_price: Option<f64>,
+    /// How much worse the average price is than the midprice, in the units of price.
+    pub slippage: Option<f64>,
+    /// Amount left once the side is exhausted, in base quantity for quantity orders and in quote
+    /// currency for notional orders.
+    pub unfilled: f64,
+}
+
+/// Which amount a market order is sized by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Amount {
+    Quantity(f64),
+    Notional(f64),
+}
+
+/// Walks `levels`, ordered from the best price, until `amount` is filled.
+pub(crate) fn walk<'a>(
+    levels: impl Iterator<Item = &'a Level>,
+    is_buy: bool,
+    amount: Amount,
+    midprice: Option<f64>,
+) -> Execution {
+    let mut remaining = match amount {
+        Amount::Quantity(quantity) => quantity,
+        Amount::Notional(notional) => notional,
+    };
+
+    let mut fills = Vec::new();
+    for level in levels {
+        if remaining <= 0.0 {
+            break;
+        }
+
+        let size = match amount {
+            Amount::Quantity(_) => level.size.min(remaining),
+            Amount::Notional(_) => level.size.min(remaining / level.price),
+        };
+
+        remaining -= match amount {
+            Amount::Quantity(_) => size,
+            Amount::Notional(_) => size * level.price,
+        };
+        fills.push(Level::new(level.price, size));
+    }
+
+    let filled: f64 = fills.iter().map(|fill| fill.size).sum();
+    let notional: f64 = fills.iter().map(|fill| fill.price * fill.size).sum();
+    let average_price = (filled > 0.0).then(|| notional / filled);
+
+    let slippage = match (average_price, midprice) {
+        (Some(average), Some(mid)) if is_buy => Some(average - mid),
+        (Some(average), Some(mid)) => Some(mid - average),
+        _ => None,
+    };
+
+    Execution {
+        worst_price: fills.last().map(|fill| fill.price),
+        fills,
+        filled,
+        notional,
+        average_price,
+        slippage,
+        unfilled: remaining.max(0.0),
+    }
+}
@@ -0,0 +1,260 @@
+use crate::event::Event;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trade {
+    pub timestamp: u64,
+    pub is_buy: bool,
+    pub price: f64,
+    pub size: f64,
+}
+
+/// Volume and counts of a set of trades, split by side.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TradeStats {
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+    pub notional: f64,
+    pub buys: u64,
+    pub sells: u64,
+}
+
+impl TradeStats {
+    fn add(&mut self, trade: &Trade) {
+        match trade.is_buy {
+            true => {
+                self.buy_volume += trade.size;
+                self.buys += 1;
+            }
+            false => {
+                self.sell_volume += trade.size;
+                self.sells += 1;
+            }
+        }
+        self.notional += trade.price * trade.size;
+    }
+
+    fn remove(&mut self, trade: &Trade) {
+        match trade.is_buy {
+            true => {
+                self.buy_volume -= trade.size;
+                self.buys -= 1;
+            }
+            false => {
+                self.sell_volume -= trade.size;
+                self.sells -= 1;
+            }
+        }
+        self.notional -= trade.price * trade.size;
+
+        if self.count() == 0 {
+            *self = Self::default();
+        }
+    }
+
+    pub fn volume(&self) -> f64 {
+        self.buy_volume + self.sell_volume
+    }
+
+    /// Buy volume minus sell volume.
+    pub fn signed_volume(&self) -> f64 {
+        self.buy_volume - self.sell_volume
+    }
+
+    pub fn count(&self) -> u64 {
+        self.buys + self.sells
+    }
+
+    pub fn vwap(&self) -> Option<f64> {
+        let volume = self.volume();
+        (volume > 0.0).then(|| self.notional / volume)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RollingWindow {
+    span: u64,
+    /// Position in the tape of the first trade in the window.
+    start: usize,
+    stats: TradeStats,
+}
+
+/// Record of the trades in an event stream, with statistics since the start and over rolling
+/// time windows.
+///
+/// Sides are taken from `is_buy` as given, so the signs are reversed for feeds that flag the
+/// side of the resting order.
+#[derive(Debug, Clone)]
+pub struct Tape {
+    last: Option<Trade>,
+    total: TradeStats,
+    windows: Vec<RollingWindow>,
+    trades: VecDeque<Trade>,
+    /// Position in the tape of the front of `trades`.
+    head: usize,
+    now: u64,
+}
+
+impl Tape {
+    /// Keeps rolling statistics over each of `windows`, in microseconds.
+    pub fn new(windows: &[u64]) -> Self {
+        Self {
+            last: None,
+            total: TradeStats::default(),
+            windows: windows
+                .iter()
+                .map(|&span| RollingWindow {
+                    span,
+                    start: 0,
+                    stats: TradeStats::default(),
+                })
+                .collect(),
+            trades: VecDeque::new(),
+            head: 0,
+            now: 0,
+        }
+    }
+
+    /// Records a trade event and moves the windows forward to its timestamp. Other events only
+    /// move the windows.
+    pub fn process(&mut self, event: Event) {
+        if event.is_trade {
+            let trade = Trade {
+                timestamp: event.timestamp,
+                is_buy: event.is_buy,
+                price: event.price,
+                size: event.size,
+            };
+
+            self.total.add(&trade);
+            self.windows
+                .iter_mut()
+                .for_each(|window| window.stats.add(&trade));
+            self.trades.push_back(trade);
+            self.last = Some(trade);
+        }
+
+        self.advance(event.timestamp);
+    }
+
+    /// Drops the trades that fell out of the windows at `now`.
+    pub fn advance(&mut self, now: u64) {
+        self.now = self.now.max(now);
+
+        for window in self.windows.iter_mut() {
+            while let Some(trade) = self.trades.get(window.start - self.head) {
+                if self.now.saturating_sub(trade.timestamp) < window.span {
+                    break;
+                }
+                window.stats.remove(trade);
+                window.start += 1;
+            }
+        }
+
+        let start = self
+            .windows
+            .iter()
+            .map(|window| window.start)
+            .min()
+            .unwrap_or(self.head + self.trades.len());
+        self.trades.drain(..start - self.head);
+        self.head = start;
+    }
+
+    pub fn last(&self) -> Option<Trade> {
+        self.last
+    }
+
+    /// Statistics of every trade seen.
+    pub fn total(&self) -> &TradeStats {
+        &self.total
+    }
+
+    /// Statistics of the trades within the `i`-th window given to [`Tape::new`].
+    pub fn window(&self, i: usize) -> Option<&TradeStats> {
+        self.windows.get(i).map(|window| &window.stats)
+    }
+
+    /// Volume weighted average price of every trade seen.
+    pub fn vwap(&self) -> Option<f64> {
+        self.total.vwap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolling_windows() {
+        let mut tape = Tape::new(&[10, 100]);
+
+        let event = Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: true,
+            is_buy: true,
+            price: 100.0,
+            size: 2.0,
+        };
+
+        tape.process(event);
+        tape.process(Event {
+            timestamp: 5,
+            seq: 1,
+            is_buy: false,
+            price: 102.0,
+            ..event
+        });
+        tape.process(Event {
+            timestamp: 8,
+            seq: 2,
+            is_trade: false,
+            ..event
+        });
+
+        assert_eq!(
+            tape.last(),
+            Some(Trade {
+                timestamp: 5,
+                is_buy: false,
+                price: 102.0,
+                size: 2.0
+            })
+        );
+        assert_eq!(tape.vwap(), Some(101.0));
+        assert_eq!(tape.window(0).unwrap().count(), 2);
+
+        tape.process(Event {
+            timestamp: 12,
+            seq: 3,
+            size: 1.0,
+            ..event
+        });
+
+        let short = tape.window(0).unwrap();
+        assert_eq!(short.signed_volume(), -1.0);
+        assert_eq!((short.buys, short.sells), (1, 1));
+
+        let long = tape.window(1).unwrap();
+        assert_eq!(long.signed_volume(), 1.0);
+        assert_eq!(long.count(), 3);
+
+        tape.advance(200);
+
+        assert_eq!(tape.window(1).unwrap().count(), 0);
+        assert_eq!(tape.total().count(), 3);
+        assert_eq!(tape.total().buy_volume, 3.0);
+        assert!(tape.window(2).is_none());
+
+        let mut tape = Tape::new(&[u64::MAX]);
+
+        tape.process(Event {
+            timestamp: u64::MAX - 1,
+            ..event
+        });
+        tape.advance(u64::MAX);
+
+        assert_eq!(tape.window(0).unwrap().count(), 1);
+    }
+}
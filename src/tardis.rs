@@ -0,0 +1,373 @@
+use crate::{
+    event::{deserialize_flag, deserialize_side, Event},
+    level::Level,
+    orderbook::Orderbook,
+    schema::SchemaError,
+};
+use csv::{Reader, StringRecord};
+use serde::Deserialize;
+use std::io::Read;
+
+/// Row of a Tardis `incremental_book_L2` CSV export
+/// (`exchange,symbol,timestamp,local_timestamp,is_snapshot,side,price,amount`).
+/// Timestamps are microseconds since epoch and `amount` is the new absolute size of the level.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BookChange {
+    pub exchange: String,
+    pub symbol: String,
+    pub timestamp: u64,
+    pub local_timestamp: u64,
+    #[serde(deserialize_with = "deserialize_flag")]
+    pub is_snapshot: bool,
+    #[serde(rename = "side", deserialize_with = "deserialize_side")]
+    pub is_buy: bool,
+    pub price: f64,
+    pub amount: f64,
+}
+
+impl BookChange {
+    pub fn event(&self, seq: u64) -> Event {
+        Event {
+            timestamp: self.timestamp,
+            seq,
+            is_trade: false,
+            is_buy: self.is_buy,
+            price: self.price,
+            size: self.amount,
+        }
+    }
+}
+
+pub fn read_incremental<R: Read>(
+    reader: Reader<R>,
+) -> impl Iterator<Item = Result<BookChange, SchemaError>> {
+    reader
+        .into_deserialize::<BookChange>()
+        .map(|row| row.map_err(SchemaError::from))
+}
+
+/// Row of a Tardis `book_snapshot_{depth}_{interval}` CSV export, holding the top levels of each
+/// side (best first) in the `asks[i].price`, `asks[i].amount`, `bids[i].price` and
+/// `bids[i].amount` columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookSnapshot {
+    pub exchange: String,
+    pub symbol: String,
+    pub timestamp: u64,
+    pub local_timestamp: u64,
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+}
+
+pub fn read_snapshots<R: Read>(mut reader: Reader<R>) -> Result<SnapshotReader<R>, SchemaError> {
+    let headers = reader.headers()?.clone();
+    let find = |column: &str| headers.iter().position(|header| header.trim() == column);
+    let require =
+        |column: &str| find(column).ok_or_else(|| SchemaError::MissingColumn(column.to_string()));
+
+    let levels = |side: &str| {
+        (0..)
+            .map_while(|i| {
+                let price = find(&format!("{}[{}].price", side, i))?;
+                let amount = find(&format!("{}[{}].amount", side, i))?;
+                Some((price, amount))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let columns = SnapshotColumns {
+        exchange: require("exchange")?,
+        symbol: require("symbol")?,
+        timestamp: require("timestamp")?,
+        local_timestamp: require("local_timestamp")?,
+        bids: levels("bids"),
+        asks: levels("asks"),
+    };
+
+    if columns.bids.is_empty() {
+        return Err(SchemaError::MissingColumn("bids[0].price".to_string()));
+    }
+    if columns.asks.is_empty() {
+        return Err(SchemaError::MissingColumn("asks[0].price".to_string()));
+    }
+
+    Ok(SnapshotReader {
+        reader,
+        record: StringRecord::new(),
+        headers,
+        columns,
+    })
+}
+
+#[derive(Debug, Clone)]
+struct SnapshotColumns {
+    exchange: usize,
+    symbol: usize,
+    timestamp: usize,
+    local_timestamp: usize,
+    bids: Vec<(usize, usize)>,
+    asks: Vec<(usize, usize)>,
+}
+
+/// Iterator over the rows of a `book_snapshot` CSV export.
+#[derive(Debug)]
+pub struct SnapshotReader<R> {
+    reader: Reader<R>,
+    record: StringRecord,
+    headers: StringRecord,
+    columns: SnapshotColumns,
+}
+
+impl<R: Read> SnapshotReader<R> {
+    fn parse_record(&self) -> Result<BookSnapshot, SchemaError> {
+        let row = self.record.position().map_or(0, |pos| pos.line());
+        let value = |index: usize| self.record.get(index).map_or("", str::trim);
+        let invalid = |index: usize| SchemaError::InvalidValue {
+            row,
+            column: self.headers.get(index).unwrap_or_default().to_string(),
+            value: value(index).to_string(),
+        };
+
+        let timestamp = value(self.columns.timestamp)
+            .parse()
+            .map_err(|_| invalid(self.columns.timestamp))?;
+        let local_timestamp = value(self.columns.local_timestamp)
+            .parse()
+            .map_err(|_| invalid(self.columns.local_timestamp))?;
+
+        // levels missing from a shallow book are left empty
+        let levels = |columns: &[(usize, usize)]| {
+            let mut levels = Vec::with_capacity(columns.len());
+            for &(price, amount) in columns {
+                if value(price).is_empty() || value(amount).is_empty() {
+                    break;
+                }
+                levels.push(Level::new(
+                    value(price).parse().map_err(|_| invalid(price))?,
+                    value(amount).parse().map_err(|_| invalid(amount))?,
+                ));
+            }
+            Ok::<_, SchemaError>(levels)
+        };
+
+        Ok(BookSnapshot {
+            exchange: value(self.columns.exchange).to_string(),
+            symbol: value(self.columns.symbol).to_string(),
+            timestamp,
+            local_timestamp,
+            bids: levels(&self.columns.bids)?,
+            asks: levels(&self.columns.asks)?,
+        })
+    }
+}
+
+impl<R: Read> Iterator for SnapshotReader<R> {
+    type Item = Result<BookSnapshot, SchemaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.read_record(&mut self.record) {
+            Ok(true) => Some(self.parse_record()),
+            Ok(false) => None,
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+/// Applies Tardis exports to an [`Orderbook`].
+///
+/// A run of `is_snapshot` rows following regular updates starts a new snapshot, so the book is
+/// cleared before its first row is applied. Events are sequenced by the number of applied rows.
+#[derive(Debug, Clone)]
+pub struct TardisBook {
+    book: Orderbook,
+    in_snapshot: bool,
+    seq: u64,
+}
+
+impl TardisBook {
+    pub fn new(tick_size: f64) -> Self {
+        Self::from_orderbook(Orderbook::new(tick_size))
+    }
+
+    pub fn from_orderbook(book: Orderbook) -> Self {
+        Self {
+            book,
+            in_snapshot: false,
+            seq: 0,
+        }
+    }
+
+    pub fn book(&self) -> &Orderbook {
+        &self.book
+    }
+
+    pub fn into_inner(self) -> Orderbook {
+        self.book
+    }
+
+    /// Applies an `incremental_book_L2` row, returning the event fed to the book.
+    pub fn apply_change(&mut self, change: &BookChange) -> Event {
+        if change.is_snapshot && !self.in_snapshot {
+            self.book.clear();
+        }
+        self.in_snapshot = change.is_snapshot;
+
+        self.seq += 1;
+        let event = change.event(self.seq);
+        self.book.process(event);
+
+        event
+    }
+
+    /// Replaces the book with the levels of a `book_snapshot` row.
+    pub fn apply_snapshot(&mut self, snapshot: &BookSnapshot) {
+        self.book.clear();
+        self.in_snapshot = false;
+        self.seq += 1;
+
+        let to_event = |is_buy: bool| {
+            let seq = self.seq;
+            move |level: &Level| Event {
+                timestamp: snapshot.timestamp,
+                seq,
+                is_trade: false,
+                is_buy,
+                price: level.price,
+                size: level.size,
+            }
+        };
+
+        snapshot
+            .bids
+            .iter()
+            .map(to_event(true))
+            .chain(snapshot.asks.iter().map(to_event(false)))
+            .for_each(|event| self.book.process(event));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INCREMENTAL: &str = "\
+exchange,symbol,timestamp,local_timestamp,is_snapshot,side,price,amount
+deribit,BTC-PERPETUAL,1585699200245000,1585699200355684,true,ask,6443.5,38640
+deribit,BTC-PERPETUAL,1585699200245000,1585699200355684,true,bid,6443,1000
+deribit,BTC-PERPETUAL,1585699200245000,1585699200355684,true,bid,6442.5,500
+deribit,BTC-PERPETUAL,1585699200251000,1585699200362000,false,bid,6443,0
+deribit,BTC-PERPETUAL,1585699200251000,1585699200362000,false,ask,6444,10
+deribit,BTC-PERPETUAL,1585699300000000,1585699300100000,true,ask,6500,1
+deribit,BTC-PERPETUAL,1585699300000000,1585699300100000,true,bid,6499,2
+";
+
+    #[test]
+    fn read_incremental_rows() {
+        let reader = csv::Reader::from_reader(INCREMENTAL.as_bytes());
+
+        let rows = read_incremental(reader)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(rows.len(), 7);
+        assert_eq!(rows[0].exchange, "deribit");
+        assert!(rows[0].is_snapshot);
+        assert!(!rows[0].is_buy);
+        assert!(rows[1].is_buy);
+        assert_eq!(rows[3].amount, 0.0);
+
+        let event = rows[1].event(2);
+
+        assert_eq!(event.timestamp, 1585699200245000);
+        assert_eq!(event.seq, 2);
+        assert_eq!(event.price, 6443.0);
+        assert_eq!(event.size, 1000.0);
+    }
+
+    #[test]
+    fn snapshot_runs_reset_the_book() {
+        let reader = csv::Reader::from_reader(INCREMENTAL.as_bytes());
+        let mut tardis = TardisBook::new(0.5);
+
+        let rows = read_incremental(reader)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        for row in &rows[..5] {
+            tardis.apply_change(row);
+        }
+
+        assert_eq!(tardis.book().best_bid(), Some(Level::new(6442.5, 500.0)));
+        assert_eq!(
+            tardis.book().top_asks(5),
+            [Level::new(6443.5, 38640.0), Level::new(6444.0, 10.0)]
+        );
+
+        for row in &rows[5..] {
+            tardis.apply_change(row);
+        }
+
+        assert_eq!(tardis.book().top_bids(5), [Level::new(6499.0, 2.0)]);
+        assert_eq!(tardis.book().top_asks(5), [Level::new(6500.0, 1.0)]);
+    }
+
+    #[test]
+    fn read_book_snapshots() {
+        let data = "\
+exchange,symbol,timestamp,local_timestamp,asks[0].price,asks[0].amount,bids[0].price,bids[0].amount,asks[1].price,asks[1].amount,bids[1].price,bids[1].amount
+binance,BTCUSDT,1599868800000000,1599868800003000,10364.5,1.5,10364.4,0.3,10365.0,2,10364.0,4
+binance,BTCUSDT,1599868810000000,1599868810003000,10366.0,1,10365.5,0.5,,,,
+";
+        let reader = csv::Reader::from_reader(data.as_bytes());
+
+        let snapshots = read_snapshots(reader)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(
+            snapshots[0].bids,
+            [Level::new(10364.4, 0.3), Level::new(10364.0, 4.0)]
+        );
+        assert_eq!(
+            snapshots[0].asks,
+            [Level::new(10364.5, 1.5), Level::new(10365.0, 2.0)]
+        );
+        assert_eq!(snapshots[1].asks, [Level::new(10366.0, 1.0)]);
+
+        let mut tardis = TardisBook::new(0.1);
+
+        tardis.apply_snapshot(&snapshots[0]);
+
+        assert_eq!(tardis.book().best_bid(), Some(Level::new(10364.4, 0.3)));
+
+        tardis.apply_snapshot(&snapshots[1]);
+
+        assert_eq!(tardis.book().top_bids(5), [Level::new(10365.5, 0.5)]);
+        assert_eq!(tardis.book().top_asks(5), [Level::new(10366.0, 1.0)]);
+    }
+
+    #[test]
+    fn snapshot_errors() {
+        let data = "exchange,symbol,timestamp,local_timestamp,asks[0].price,asks[0].amount\n";
+        let reader = csv::Reader::from_reader(data.as_bytes());
+
+        assert_eq!(
+            read_snapshots(reader).unwrap_err().to_string(),
+            "missing column `bids[0].price`"
+        );
+
+        let data = "\
+exchange,symbol,timestamp,local_timestamp,asks[0].price,asks[0].amount,bids[0].price,bids[0].amount
+binance,BTCUSDT,1,1,abc,1,1,1
+";
+        let reader = csv::Reader::from_reader(data.as_bytes());
+        let err = read_snapshots(reader).unwrap().next().unwrap().unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "row 2: invalid value \"abc\" for column `asks[0].price`"
+        );
+    }
+}
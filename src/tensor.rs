@@ -0,0 +1,225 @@
+use crate::{event::Event, orderbook::Orderbook};
+use std::collections::VecDeque;
+
+/// How prices and sizes are scaled in a tensor.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    #[default]
+    Raw,
+    /// Prices and sizes are each standardized by their mean and deviation over the window.
+    ZScore,
+    /// Prices are relative to the midprice of their update, sizes are left as is.
+    Midprice,
+}
+
+/// Rolling windows of the top levels of a book, in the layout of DeepLOB.
+///
+/// Each update is a row of `depth` groups of ask price, ask size, bid price and bid size, best
+/// level first. Levels missing from the book are 0 after normalization.
+#[derive(Debug, Clone)]
+pub struct TensorBuilder {
+    book: Orderbook,
+    depth: usize,
+    window: usize,
+    normalization: Normalization,
+    rows: VecDeque<(Option<f64>, Vec<f64>)>,
+}
+
+impl TensorBuilder {
+    pub fn new(book: Orderbook, depth: usize, window: usize) -> Self {
+        Self {
+            book,
+            depth,
+            window: window.max(1),
+            normalization: Normalization::Raw,
+            rows: VecDeque::new(),
+        }
+    }
+
+    pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    pub fn book(&self) -> &Orderbook {
+        &self.book
+    }
+
+    pub fn into_inner(self) -> Orderbook {
+        self.book
+    }
+
+    /// Shape of the tensors, as window length and features per update.
+    pub fn shape(&self) -> [usize; 2] {
+        [self.window, self.depth * 4]
+    }
+
+    /// Processes an event, returning the tensor of the last updates once the window is full,
+    /// flattened row by row.
+    pub fn process(&mut self, event: Event) -> Option<Vec<f32>> {
+        self.book.process(event);
+
+        let mut row = vec![f64::NAN; self.depth * 4];
+        for (i, ask) in self.book.asks().take(self.depth).enumerate() {
+            row[i * 4] = ask.price;
+            row[i * 4 + 1] = ask.size;
+        }
+        for (i, bid) in self.book.bids().take(self.depth).enumerate() {
+            row[i * 4 + 2] = bid.price;
+            row[i * 4 + 3] = bid.size;
+        }
+
+        if self.rows.len() == self.window {
+            self.rows.pop_front();
+        }
+        self.rows.push_back((self.book.midprice(), row));
+
+        (self.rows.len() == self.window).then(|| self.tensor())
+    }
+
+    fn tensor(&self) -> Vec<f32> {
+        let is_price = |i: usize| matches!(i % 4, 0 | 2);
+
+        let (prices, sizes) = match self.normalization {
+            Normalization::ZScore => (self.moments(is_price), self.moments(|i| !is_price(i))),
+            _ => ((0.0, 1.0), (0.0, 1.0)),
+        };
+
+        self.rows
+            .iter()
+            .flat_map(|(midprice, row)| {
+                row.iter().enumerate().map(move |(i, &value)| {
+                    let value = match (self.normalization, is_price(i)) {
+                        (Normalization::ZScore, true) => (value - prices.0) / prices.1,
+                        (Normalization::ZScore, false) => (value - sizes.0) / sizes.1,
+                        (Normalization::Midprice, true) => match midprice {
+                            Some(mid) => value / mid - 1.0,
+                            None => f64::NAN,
+                        },
+                        _ => value,
+                    };
+
+                    match value.is_finite() {
+                        true => value as f32,
+                        false => 0.0,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Mean and standard deviation of the present values of the selected features.
+    fn moments(&self, selected: impl Fn(usize) -> bool) -> (f64, f64) {
+        let values = || {
+            self.rows.iter().flat_map(|(_, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|&(i, value)| selected(i) && !value.is_nan())
+                    .map(|(_, &value)| value)
+            })
+        };
+
+        let count = values().count() as f64;
+        if count == 0.0 {
+            return (0.0, 1.0);
+        }
+
+        let mean = values().sum::<f64>() / count;
+        let variance = values().map(|value| (value - mean).powi(2)).sum::<f64>() / count;
+
+        match variance > 0.0 {
+            true => (mean, variance.sqrt()),
+            false => (mean, 1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::npy::NpyWriter;
+    use std::io::Cursor;
+
+    #[test]
+    fn rolling_tensors() {
+        let mut builder = TensorBuilder::new(Orderbook::new(0.01), 2, 2);
+
+        let event = Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 99.0,
+            size: 1.0,
+        };
+
+        assert_eq!(builder.process(event), None);
+
+        let tensor = builder
+            .process(Event {
+                seq: 1,
+                is_buy: false,
+                price: 101.0,
+                size: 2.0,
+                ..event
+            })
+            .unwrap();
+
+        assert_eq!(builder.shape(), [2, 8]);
+        assert_eq!(
+            tensor,
+            [
+                0.0, 0.0, 99.0, 1.0, 0.0, 0.0, 0.0, 0.0, //
+                101.0, 2.0, 99.0, 1.0, 0.0, 0.0, 0.0, 0.0,
+            ]
+        );
+
+        let mut writer = NpyWriter::new(Cursor::new(Vec::new()), &builder.shape()).unwrap();
+        writer.write(&tensor).unwrap();
+        assert_eq!(writer.len(), 1);
+    }
+
+    #[test]
+    fn normalization() {
+        let mut builder = TensorBuilder::new(Orderbook::new(0.01), 1, 1)
+            .with_normalization(Normalization::Midprice);
+
+        let event = Event {
+            timestamp: 0,
+            seq: 0,
+            is_trade: false,
+            is_buy: true,
+            price: 99.0,
+            size: 1.0,
+        };
+
+        builder.process(event);
+        let tensor = builder
+            .process(Event {
+                seq: 1,
+                is_buy: false,
+                price: 101.0,
+                size: 3.0,
+                ..event
+            })
+            .unwrap();
+
+        assert_eq!(tensor, [0.01, 3.0, -0.01, 1.0]);
+
+        let mut builder = TensorBuilder::new(Orderbook::new(0.01), 1, 1)
+            .with_normalization(Normalization::ZScore);
+
+        builder.process(event);
+        let tensor = builder
+            .process(Event {
+                seq: 1,
+                is_buy: false,
+                price: 101.0,
+                size: 3.0,
+                ..event
+            })
+            .unwrap();
+
+        assert_eq!(tensor, [1.0, 1.0, -1.0, -1.0]);
+    }
+}
@@ -0,0 +1,142 @@
+//! Conversions of textual UTC timestamps to microseconds since epoch.
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    let (next_year, next_month) = match month {
+        12 => (year as i64 + 1, 1),
+        _ => (year as i64, month + 1),
+    };
+
+    (days_from_civil(next_year, next_month, 1) - days_from_civil(year as i64, month, 1)) as u32
+}
+
+fn number(value: &str) -> Option<u32> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// Parses `HH:MM:SS[.f]` into microseconds since midnight, dropping digits past microseconds.
+fn time_of_day(value: &str) -> Option<u64> {
+    let (time, fraction) = match value.split_once('.') {
+        Some((time, fraction)) => (time, fraction),
+        None => (value, ""),
+    };
+
+    let mut parts = time.split(':');
+    let hours = number(parts.next()?)?;
+    let minutes = number(parts.next()?)?;
+    let seconds = number(parts.next()?)?;
+    if parts.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    let mut micros = 0;
+    if !fraction.is_empty() {
+        number(fraction)?;
+        micros = fraction
+            .bytes()
+            .chain(std::iter::repeat(b'0'))
+            .take(6)
+            .fold(0, |acc, b| acc * 10 + (b - b'0') as u64);
+    }
+
+    Some(((hours as u64 * 60 + minutes as u64) * 60 + seconds as u64) * 1_000_000 + micros)
+}
+
+fn date_micros(year: u32, month: u32, day: u32, time: &str) -> Option<u64> {
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let days = days_from_civil(year as i64, month, day);
+    let days = u64::try_from(days).ok()?;
+
+    Some(days * 86_400_000_000 + time_of_day(time)?)
+}
+
+/// Parses a FIX `UTCTimestamp` (`YYYYMMDD-HH:MM:SS[.sss]`).
+pub(crate) fn parse_fix_timestamp(value: &str) -> Option<u64> {
+    let (date, time) = value.split_once('-')?;
+    if date.len() != 8 || !date.is_ascii() {
+        return None;
+    }
+
+    date_micros(
+        number(&date[0..4])?,
+        number(&date[4..6])?,
+        number(&date[6..8])?,
+        time,
+    )
+}
+
+/// Parses an RFC 3339 UTC timestamp (`YYYY-MM-DDTHH:MM:SS[.f][Z|+00:00]`).
+pub(crate) fn parse_rfc3339(value: &str) -> Option<u64> {
+    let value = value
+        .strip_suffix('Z')
+        .or_else(|| value.strip_suffix("+00:00"))
+        .unwrap_or(value);
+    let (date, time) = value.split_once(['T', ' '])?;
+
+    let mut parts = date.split('-');
+    let year = number(parts.next()?)?;
+    let month = number(parts.next()?)?;
+    let day = number(parts.next()?)?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    date_micros(year, month, day, time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fix_timestamps() {
+        assert_eq!(parse_fix_timestamp("19700101-00:00:00"), Some(0));
+        assert_eq!(
+            parse_fix_timestamp("20191201-00:00:05.045"),
+            Some(1575158405045000)
+        );
+        assert_eq!(
+            parse_fix_timestamp("20240229-23:59:59.123456789"),
+            Some(1709251199123456)
+        );
+        assert_eq!(parse_fix_timestamp("20240230-00:00:00"), None);
+        assert_eq!(parse_fix_timestamp("20230229-00:00:00"), None);
+        assert_eq!(parse_fix_timestamp("20240431-00:00:00"), None);
+        assert_eq!(parse_fix_timestamp("20240101-24:00:00"), None);
+        assert_eq!(parse_fix_timestamp("202é010-00:00:00"), None);
+        assert_eq!(parse_fix_timestamp("2024-01-01T00:00:00"), None);
+    }
+
+    #[test]
+    fn rfc3339_timestamps() {
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_rfc3339("2023-02-09T20:32:50.714964855Z"),
+            Some(1675974770714964)
+        );
+        assert_eq!(
+            parse_rfc3339("2014-11-07T08:19:27.028459+00:00"),
+            Some(1415348367028459)
+        );
+        assert_eq!(parse_rfc3339("2014-11-07"), None);
+        assert_eq!(parse_rfc3339("2100-02-29T00:00:00Z"), None);
+        assert_eq!(parse_rfc3339("20141107-08:19:27"), None);
+    }
+}